@@ -1,20 +1,35 @@
 use crate::ffmpeg_download::download_with_progress;
-use crate::ffmpeg_export_command::{ExportOptions, GpuAcceleration};
+use crate::ffmpeg_export::{
+    ExportPlan, concat_export_plan, reencode_export_plan, smart_render_export_plan, stream_copy_export_plan, target_size_video_bitrate_kbps,
+    two_pass_export_plan,
+};
+use crate::ffmpeg_export_command::{BurnInSubtitles, ConcatOptions, ExportMode, ExportOptions, TimeRange};
 use crate::ffmpeg_path::{ffmpeg_is_installed, ffmpeg_path};
+use crate::ffmpeg_process::{SUSPEND_SUPPORTED, resume_process, suspend_process};
+use crate::ffmpeg_queue_store::{save_ffmpeg_queue, unix_time_millis};
+use crate::ffmpeg_scheduler::{FfmpegSchedulerState, FfmpegTaskSlot};
+use crate::ffmpeg_task_error::{FfmpegTaskError, FfmpegTaskFailure};
+use crate::ffmpeg_task_log::SharedFfmpegTaskLog;
 use crate::ffmpeg_time_duration::FfmpegTimeDuration;
-use crate::ffprobe::{get_video_audio_streams_info, get_video_streams_info};
-use crate::select_new_video_file_command::AudioStreamFilePath;
+use crate::ffprobe::{MediaStreamKind, VideoAudioStreamsInfo, probe_media_blocking};
+use crate::select_new_video_file_command::{AudioStreamFilePath, SubtitleStreamFilePath};
 use crate::{APP_HANDLE, APP_IDENTIFIER};
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
+use ffmpeg_sidecar::child::FfmpegChild;
 use ffmpeg_sidecar::command::FfmpegCommand;
 use ffmpeg_sidecar::event::{FfmpegEvent, FfmpegProgress, LogLevel};
-use log::{debug, error, info};
+use log::{debug, error, info, trace, warn};
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
 use std::io::Write;
+use std::path::Path;
 use std::process::ChildStdin;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 use tauri::window::{ProgressBarState, ProgressBarStatus};
 use tauri::{Emitter, Manager, async_runtime};
 use tokio::sync::{Mutex, MutexGuard, RwLock, mpsc, oneshot};
@@ -43,34 +58,297 @@ const WEB_SUPPORTED_AUDIO_CODECS_CONTAINERS: [&str; 9] = [
     "wav",  // pcm_f32le
 ];
 
+/// How many last ffmpeg error lines are kept for the failure reason
+const FFMPEG_ERROR_LINES: usize = 10;
+
+/// Weight of the newest speed sample in the smoothed speed used for ETA
+const SPEED_SMOOTHING_FACTOR: f64 = 0.2;
+
+/// Progress events closer than this are not used as speed samples
+const SPEED_MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Minimal interval between progress updates of a task sent to the frontend
+const FFMPEG_PROGRESS_EMIT_INTERVAL: Duration = Duration::from_millis(250);
+
+const FFMPEG_TASK_UPDATE_EVENT: &str = "ffmpeg-task-update";
+
+/// How long ffmpeg may take to quit after `q` before it is killed
+const FFMPEG_CANCEL_TIMEOUT: Duration = Duration::from_secs(5);
+
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(1);
+
+const TARGET_SIZE_MAX_ATTEMPTS: u32 = 3;
+
+/// Additional bitrate reduction on top of the overshoot ratio when retrying a target size export
+const TARGET_SIZE_RETRY_BITRATE_FACTOR: f64 = 0.97;
+
+/// Share of the remaining task progress a target size attempt moves through, the rest is left for retries
+const TARGET_SIZE_ATTEMPT_PROGRESS_SHARE: f64 = 0.8;
+
 #[derive(Debug, Serialize, Deserialize, ts_rs::TS, Clone)]
 #[serde(rename_all = "camelCase")]
 #[ts(export)]
 pub struct FfmpegTask {
+    /// Stable across queue changes and restarts
+    #[ts(type = "number")]
+    #[serde(default)]
+    id: u64,
     status: FfmpegTaskStatus,
     task_type: FfmpegTaskType,
+    /// Queued tasks with higher priority start first, equal ones in queue order
+    priority: i32,
+    /// Unix time in milliseconds
+    #[ts(type = "number")]
+    created_at: u64,
+    /// Unix time in milliseconds when the task finished, failed or was cancelled
+    #[ts(type = "number | null")]
+    finished_at: Option<u64>,
+    /// Milliseconds the task was running, excluding pauses. Stored on the task instead of its result,
+    /// so failed and cancelled tasks, which have no result, report it too.
+    #[ts(type = "number | null")]
+    #[serde(default)]
+    elapsed_millis: Option<u64>,
     #[serde(skip)]
     ffmpeg_stdin: Option<mpsc::Sender<String>>,
+    #[serde(skip)]
+    ffmpeg_process: Option<FfmpegProcess>,
+    #[serde(skip)]
+    log: SharedFfmpegTaskLog,
+    #[serde(skip)]
+    last_progress_emit: Option<Instant>,
+    #[serde(skip)]
+    timing: FfmpegTaskTiming,
 }
 
 impl FfmpegTask {
     pub fn new(task_type: FfmpegTaskType) -> Self {
         Self {
+            id: NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed),
             status: FfmpegTaskStatus::Queued,
             task_type,
+            priority: 0,
+            created_at: unix_time_millis(),
+            finished_at: None,
+            elapsed_millis: None,
             ffmpeg_stdin: None,
+            ffmpeg_process: None,
+            log: SharedFfmpegTaskLog::default(),
+            last_progress_emit: None,
+            timing: FfmpegTaskTiming::default(),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn log(&self) -> SharedFfmpegTaskLog {
+        self.log.clone()
+    }
+
+    /// Keeps the id of a restored task unique, tasks saved without an id get a new one
+    pub fn restore_id(&mut self) {
+        if self.id == 0 {
+            self.id = NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed);
+        } else {
+            NEXT_TASK_ID.fetch_max(self.id + 1, Ordering::Relaxed);
+        }
+    }
+
+    /// Files the task reads, exports only read files allowed in the asset protocol scope
+    pub fn input_paths(&self) -> Vec<&str> {
+        match &self.task_type {
+            FfmpegTaskType::ExtractAudio { video_file_path, .. } => vec![video_file_path.as_str()],
+            FfmpegTaskType::ExportVideo { options, .. } => {
+                let mut paths = vec![options.input_path.as_str()];
+                if let Some(BurnInSubtitles::File { path }) = &options.burn_in_subtitles {
+                    paths.push(path.as_str());
+                }
+                paths
+            }
+            FfmpegTaskType::ConcatVideos { options, .. } => options.clips.iter().map(|clip| clip.input_path.as_str()).collect(),
+            FfmpegTaskType::DownloadFfmpeg { .. } => vec![],
         }
     }
+
+    /// Whether the task is saved to be restored after restart,
+    /// audio extraction and ffmpeg download only make sense in the session they were started in
+    pub fn is_persistent(&self) -> bool {
+        matches!(self.task_type, FfmpegTaskType::ExportVideo { .. } | FfmpegTaskType::ConcatVideos { .. })
+    }
+
+    /// Time the task has left the queue, `None` if it's still queued or running
+    pub fn finished_at(&self) -> Option<u64> {
+        match self.status {
+            FfmpegTaskStatus::Queued | FfmpegTaskStatus::InProgress { .. } | FfmpegTaskStatus::Paused { .. } => None,
+            _ => Some(self.finished_at.unwrap_or(self.created_at)),
+        }
+    }
+
+    /// Marks the task as interrupted if it was in progress when the app was closed
+    pub fn interrupt(&mut self) {
+        if matches!(self.status, FfmpegTaskStatus::InProgress { .. } | FfmpegTaskStatus::Paused { .. }) {
+            self.status = FfmpegTaskStatus::Interrupted;
+            self.finished_at = Some(unix_time_millis());
+        }
+    }
+}
+
+/// Running ffmpeg of a task
+#[derive(Clone)]
+struct FfmpegProcess {
+    /// Used to pause the process
+    pid: u32,
+    /// Used to kill the process, locked while it is awaited
+    child: Arc<std::sync::Mutex<FfmpegChild>>,
+}
+
+impl fmt::Debug for FfmpegProcess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FfmpegProcess").field("pid", &self.pid).finish()
+    }
+}
+
+/// Wall clock time of a running task without the time it was paused
+#[derive(Debug, Clone, Default)]
+struct FfmpegTaskTiming {
+    started_at: Option<Instant>,
+    paused_at: Option<Instant>,
+    paused_duration: Duration,
+    /// Running time and processed media seconds of the last speed sample
+    last_speed_sample: Option<(Duration, f64)>,
+    smoothed_speed: Option<f64>,
+}
+
+impl FfmpegTaskTiming {
+    fn start(&mut self) {
+        *self = Self {
+            started_at: Some(Instant::now()),
+            ..Default::default()
+        };
+    }
+
+    fn pause(&mut self) {
+        self.paused_at.get_or_insert_with(Instant::now);
+    }
+
+    fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused_duration += paused_at.elapsed();
+        }
+    }
+
+    /// Time since the task started, not counting pauses
+    fn active_elapsed(&self) -> Duration {
+        let Some(started_at) = self.started_at else {
+            return Duration::ZERO;
+        };
+        let paused_duration = self.paused_duration + self.paused_at.map(|paused_at| paused_at.elapsed()).unwrap_or_default();
+
+        started_at.elapsed().saturating_sub(paused_duration)
+    }
+
+    /// Forgets the last speed sample when processed media seconds start over, e.g. on a retry
+    fn restart_speed_samples(&mut self) {
+        self.last_speed_sample = None;
+    }
+
+    /// Adds a speed sample, returns processed media seconds per second of running time,
+    /// exponentially smoothed over the samples
+    fn update_speed(&mut self, processed_seconds: f64) -> Option<f64> {
+        let elapsed = self.active_elapsed();
+
+        if let Some((last_elapsed, last_processed_seconds)) = self.last_speed_sample {
+            let interval = elapsed.saturating_sub(last_elapsed);
+            if interval < SPEED_MIN_SAMPLE_INTERVAL {
+                return self.smoothed_speed;
+            }
+
+            let speed = (processed_seconds - last_processed_seconds).max(0.0) / interval.as_secs_f64();
+            self.smoothed_speed = Some(match self.smoothed_speed {
+                Some(smoothed_speed) => smoothed_speed + SPEED_SMOOTHING_FACTOR * (speed - smoothed_speed),
+                None => speed,
+            });
+        }
+
+        self.last_speed_sample = Some((elapsed, processed_seconds));
+        self.smoothed_speed
+    }
 }
 
 #[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+pub enum FfmpegTaskMove {
+    Up,
+    Down,
+    Front,
+}
+
+/// Change of the queue sent to the frontend, the whole queue is only sent when requested
+#[derive(Debug, Serialize, Clone, ts_rs::TS)]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase", tag = "type")]
+#[ts(export)]
+pub enum FfmpegTaskUpdate {
+    /// Task was added or changed
+    Changed { task: FfmpegTask },
+    /// Status of a running task, sent at most every [`FFMPEG_PROGRESS_EMIT_INTERVAL`]
+    Progress {
+        #[ts(type = "number")]
+        task_id: u64,
+        status: FfmpegTaskStatus,
+    },
+    Removed {
+        #[ts(type = "Array<number>")]
+        task_ids: Vec<u64>,
+    },
+    /// New order of all tasks in the queue
+    Reordered {
+        #[ts(type = "Array<number>")]
+        task_ids: Vec<u64>,
+    },
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
 #[serde(rename_all = "camelCase", rename_all_fields = "camelCase", tag = "type")]
 pub enum FfmpegTaskStatus {
     Queued,
-    InProgress { progress: f64 },
+    InProgress {
+        progress: f64,
+        /// Processed media seconds per second, smoothed
+        speed: Option<f64>,
+        fps: Option<f64>,
+        bitrate_kbps: Option<f64>,
+        /// Bytes written to the output of the current ffmpeg step
+        #[ts(type = "number | null")]
+        output_size: Option<u64>,
+        /// Estimated seconds left
+        eta_seconds: Option<f64>,
+    },
+    /// ffmpeg process is suspended, keeps its concurrency slot
+    Paused {
+        progress: f64,
+    },
     Finished,
-    Failed,
+    Failed {
+        reason: FfmpegTaskFailure,
+    },
     Cancelled,
+    /// Was in progress when the app was closed
+    Interrupted,
+}
+
+impl FfmpegTaskStatus {
+    /// In progress without ffmpeg statistics known yet
+    fn in_progress(progress: f64) -> Self {
+        Self::InProgress {
+            progress,
+            speed: None,
+            fps: None,
+            bitrate_kbps: None,
+            output_size: None,
+            eta_seconds: None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ts_rs::TS)]
@@ -83,9 +361,14 @@ pub enum FfmpegTaskType {
         on_complete: Option<oneshot::Sender<FfmpegAudioExtractTaskResult>>,
     },
     ExportVideo {
-        options: ExportOptions,
+        // Boxed, the options are much larger than the other task types
+        options: Box<ExportOptions>,
         result: Option<FfmpegExportVideoTaskResult>,
     },
+    ConcatVideos {
+        options: ConcatOptions,
+        result: Option<FfmpegConcatVideosTaskResult>,
+    },
     DownloadFfmpeg {
         result: Option<FfmpegDownloadTaskResult>,
     },
@@ -107,6 +390,10 @@ impl Clone for FfmpegTaskType {
                 options: options.clone(),
                 result: result.clone(),
             },
+            FfmpegTaskType::ConcatVideos { options, result } => FfmpegTaskType::ConcatVideos {
+                options: options.clone(),
+                result: result.clone(),
+            },
             FfmpegTaskType::DownloadFfmpeg { result } => FfmpegTaskType::DownloadFfmpeg { result: result.clone() },
         }
     }
@@ -115,11 +402,23 @@ impl Clone for FfmpegTaskType {
 #[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
 pub struct FfmpegAudioExtractTaskResult {
     pub audio_streams: Vec<AudioStreamFilePath>,
+    /// Text subtitle streams converted to WebVTT
+    #[serde(default)]
+    pub subtitle_streams: Vec<SubtitleStreamFilePath>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
 pub struct FfmpegExportVideoTaskResult {
     pub output_path: String,
+    /// Cut points actually used, may differ from requested ones in stream copy mode
+    pub start_time: f64,
+    pub end_time: f64,
+    pub segments: Vec<TimeRange>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+pub struct FfmpegConcatVideosTaskResult {
+    pub output_path: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
@@ -128,6 +427,15 @@ pub struct FfmpegDownloadTaskResult {
 }
 
 impl FfmpegTaskType {
+    pub fn slot(&self) -> FfmpegTaskSlot {
+        match self {
+            FfmpegTaskType::ExtractAudio { .. } => FfmpegTaskSlot::Interactive,
+            FfmpegTaskType::ExportVideo { .. } | FfmpegTaskType::ConcatVideos { .. } => FfmpegTaskSlot::Export,
+            // Other tasks need ffmpeg to be installed
+            FfmpegTaskType::DownloadFfmpeg { .. } => FfmpegTaskSlot::Exclusive,
+        }
+    }
+
     pub fn extract_audio(path: String, on_complete: Option<oneshot::Sender<FfmpegAudioExtractTaskResult>>) -> Self {
         Self::ExtractAudio {
             video_file_path: path,
@@ -137,7 +445,32 @@ impl FfmpegTaskType {
     }
 
     pub fn export_video(options: ExportOptions) -> Self {
-        Self::ExportVideo { options, result: None }
+        Self::ExportVideo {
+            options: Box::new(options),
+            result: None,
+        }
+    }
+
+    pub fn concat_videos(options: ConcatOptions) -> Self {
+        Self::ConcatVideos { options, result: None }
+    }
+
+    /// File written by the task at a path chosen by the user
+    fn output_path(&self) -> Option<&str> {
+        match self {
+            FfmpegTaskType::ExportVideo { options, .. } => Some(&options.output_path),
+            FfmpegTaskType::ConcatVideos { options, .. } => Some(&options.output_path),
+            FfmpegTaskType::ExtractAudio { .. } | FfmpegTaskType::DownloadFfmpeg { .. } => None,
+        }
+    }
+
+    fn clear_result(&mut self) {
+        match self {
+            FfmpegTaskType::ExtractAudio { result, .. } => *result = None,
+            FfmpegTaskType::ExportVideo { result, .. } => *result = None,
+            FfmpegTaskType::ConcatVideos { result, .. } => *result = None,
+            FfmpegTaskType::DownloadFfmpeg { result } => *result = None,
+        }
     }
 }
 
@@ -148,367 +481,376 @@ pub fn create_ffmpeg_tasks_queue() -> FfmpegTasksQueue {
 }
 
 pub async fn enqueue_ffmpeg_task(queue: &FfmpegTasksQueue, task: FfmpegTask) {
+    let persistent = task.is_persistent();
+
+    let task = Arc::new(RwLock::new(task));
+
     let mut queue = queue.lock().await;
-    queue.push(Arc::new(RwLock::new(task)));
+    queue.push(task.clone());
     run_next_task(queue).await;
-}
 
-pub async fn run_next_task(queue: MutexGuard<'_, Vec<Arc<RwLock<FfmpegTask>>>>) {
-    let mut has_in_progress = false;
-    let mut first_queued_task = None;
+    emit_ffmpeg_task_changed(&task).await;
 
-    for task in queue.iter() {
-        if matches!(task.read().await.status, FfmpegTaskStatus::InProgress { .. }) {
-            has_in_progress = true;
-            break;
-        }
+    if persistent {
+        save_ffmpeg_queue().await;
+    }
+}
 
-        if first_queued_task.is_none() && matches!(task.read().await.status, FfmpegTaskStatus::Queued) {
-            first_queued_task = Some(task.clone());
+/// Queued tasks in the order they are started, as queue indices
+async fn queued_tasks_order(queue: &[Arc<RwLock<FfmpegTask>>]) -> Vec<(usize, i32)> {
+    let mut queued_tasks = vec![];
+    for (index, task) in queue.iter().enumerate() {
+        let task = task.read().await;
+        if task.status == FfmpegTaskStatus::Queued {
+            queued_tasks.push((index, task.priority));
         }
     }
 
+    queued_tasks.sort_by_key(|(_, priority)| Reverse(*priority));
+    queued_tasks
+}
+
+/// Queue index the task at `task_index` is moved to, after removing it from the queue, and the priority it takes over.
+/// `order` is the start order of the queued tasks, `None` if the task is not queued or can't be moved further.
+fn queued_task_move(order: &[(usize, i32)], task_index: usize, task_move: FfmpegTaskMove) -> Option<(usize, i32)> {
+    let position = order.iter().position(|(index, _)| *index == task_index)?;
+
+    let neighbor_position = match task_move {
+        FfmpegTaskMove::Up => position.checked_sub(1),
+        FfmpegTaskMove::Down => Some(position + 1).filter(|p| *p < order.len()),
+        FfmpegTaskMove::Front => Some(0).filter(|p| *p != position),
+    };
+    let (neighbor_index, neighbor_priority) = order[neighbor_position?];
+
+    // Indices after the removed task shift by one
+    let neighbor_index = if neighbor_index > task_index {
+        neighbor_index - 1
+    } else {
+        neighbor_index
+    };
+    let insert_index = match task_move {
+        FfmpegTaskMove::Up | FfmpegTaskMove::Front => neighbor_index,
+        FfmpegTaskMove::Down => neighbor_index + 1,
+    };
+
+    Some((insert_index, neighbor_priority))
+}
+
+/// Moves a queued task in the start order by taking over the priority of the task it is moved next to.
+/// Returns `false` if the task is not queued or can't be moved further.
+pub async fn move_ffmpeg_task(queue: &FfmpegTasksQueue, task_id: u64, task_move: FfmpegTaskMove) -> bool {
+    let mut queue = queue.lock().await;
+    let Some((task_index, _)) = find_ffmpeg_task(&queue, task_id).await else {
+        return false;
+    };
+    let order = queued_tasks_order(&queue).await;
+
+    let Some((insert_index, priority)) = queued_task_move(&order, task_index, task_move) else {
+        return false;
+    };
+
+    let task = queue.remove(task_index);
+    task.write().await.priority = priority;
+    queue.insert(insert_index, task.clone());
+
+    emit_ffmpeg_queue_order(&queue).await;
     drop(queue);
+    emit_ffmpeg_task_changed(&task).await;
 
-    if !has_in_progress && let Some(next_task) = first_queued_task {
-        tokio::spawn(run_ffmpeg_task(next_task));
-    }
+    true
 }
 
-fn get_audio_file_path(video_file_path: &str, audio_stream_index: i32, format: &str) -> String {
-    let tmp_folder = std::env::temp_dir().join(APP_IDENTIFIER);
-    std::fs::create_dir_all(&tmp_folder).unwrap();
-    let audio_file_name = format!("audio_{}_{}.{}", BASE64_STANDARD.encode(video_file_path), audio_stream_index, format);
+pub async fn set_ffmpeg_task_priority(queue: &FfmpegTasksQueue, task_id: u64, priority: i32) -> bool {
+    let queue = queue.lock().await;
+    let Some((_, task)) = find_ffmpeg_task(&queue, task_id).await else {
+        return false;
+    };
+
+    task.write().await.priority = priority;
 
-    tmp_folder.join(audio_file_name).to_string_lossy().to_string()
+    drop(queue);
+    emit_ffmpeg_task_changed(&task).await;
+
+    true
 }
 
-#[allow(clippy::manual_async_fn)] // Recursive async function (Send is not auto implements)
-fn run_ffmpeg_task(ffmpeg_task: Arc<RwLock<FfmpegTask>>) -> impl Future<Output = ()> + Send {
-    async move {
-        {
-            let mut ffmpeg_task_guard = ffmpeg_task.write().await;
-            ffmpeg_task_guard.status = FfmpegTaskStatus::InProgress { progress: 0.0 };
+/// Queue index and task with `task_id`
+pub async fn find_ffmpeg_task(queue: &[Arc<RwLock<FfmpegTask>>], task_id: u64) -> Option<(usize, Arc<RwLock<FfmpegTask>>)> {
+    for (index, task) in queue.iter().enumerate() {
+        if task.read().await.id == task_id {
+            return Some((index, task.clone()));
         }
+    }
+    None
+}
 
-        emit_ffmpeg_queue_status().await;
-        set_main_window_progress_bar(Some(0.0));
+/// Queues a failed, cancelled or interrupted task again. Returns `false` if the task can't be retried.
+pub async fn retry_ffmpeg_task(queue: &FfmpegTasksQueue, task_id: u64) -> bool {
+    let queue = queue.lock().await;
+    let Some((_, task)) = find_ffmpeg_task(&queue, task_id).await else {
+        return false;
+    };
+
+    {
+        let mut task = task.write().await;
+        if !matches!(
+            task.status,
+            FfmpegTaskStatus::Failed { .. } | FfmpegTaskStatus::Cancelled | FfmpegTaskStatus::Interrupted
+        ) {
+            return false;
+        }
+        // A cancelled task is only finished once its ffmpeg process has exited
+        if task.finished_at.is_none() {
+            return false;
+        }
 
-        let ffmpeg_task_guard = ffmpeg_task.read().await;
+        task.status = FfmpegTaskStatus::Queued;
+        task.finished_at = None;
+        task.elapsed_millis = None;
+        task.task_type.clear_result();
+    }
 
-        let ffmpeg_task_clone = ffmpeg_task.clone();
+    run_next_task(queue).await;
+    emit_ffmpeg_task_changed(&task).await;
 
-        match &ffmpeg_task_guard.task_type {
-            FfmpegTaskType::ExtractAudio { video_file_path, .. } => {
-                let video_file_path = video_file_path.clone();
-                drop(ffmpeg_task_guard);
-                let ffmpeg_result = tokio::task::spawn_blocking(move || {
-                    let info = get_video_audio_streams_info(&video_file_path);
-                    if let Some(info) = info {
-                        struct AudioStreamMap<'a> {
-                            index: i32,
-                            path: String,
-                            ffmpeg_map: &'a str,
-                        }
+    true
+}
 
-                        let audio_streams: Vec<_> = info
-                            .audio_streams
-                            .iter()
-                            .skip(1)
-                            .map(|steam| {
-                                let (format, _codec_name, ffmpeg_map) =
-                                    if let Some(index) = WEB_SUPPORTED_AUDIO_CODECS.iter().position(|c| c == &steam.codec_name) {
-                                        (
-                                            WEB_SUPPORTED_AUDIO_CODECS_CONTAINERS.get(index).unwrap(),
-                                            WEB_SUPPORTED_AUDIO_CODECS.get(index).unwrap(),
-                                            "-c:a copy",
-                                        )
-                                    } else {
-                                        (&"m4a", &"aac", "-c:a aac -b:a 192k")
-                                    };
-
-                                AudioStreamMap {
-                                    index: steam.index,
-                                    path: get_audio_file_path(&video_file_path, steam.index, format),
-                                    ffmpeg_map,
-                                }
-                            })
-                            .collect();
-
-                        let result = FfmpegAudioExtractTaskResult {
-                            audio_streams: audio_streams
-                                .iter()
-                                .map(|s| AudioStreamFilePath {
-                                    path: s.path.clone(),
-                                    index: s.index,
-                                })
-                                .collect(),
-                        };
-
-                        let maps = audio_streams
-                            .iter()
-                            .map(|s| format!("-map 0:{} {} {}", s.index, s.ffmpeg_map, s.path))
-                            .collect::<Vec<_>>()
-                            .join(" ");
-
-                        if maps.is_empty() {
-                            return Some(result);
-                        }
+/// Queues a copy of the task, export tasks can be duplicated with different `options`.
+/// Returns id of the new task.
+pub async fn duplicate_ffmpeg_task(queue: &FfmpegTasksQueue, task_id: u64, options: Option<ExportOptions>) -> Option<u64> {
+    let task_type = {
+        let queue = queue.lock().await;
+        let (_, task) = find_ffmpeg_task(&queue, task_id).await?;
+        task.read().await.task_type.clone()
+    };
+
+    let task_type = match (task_type, options) {
+        (FfmpegTaskType::ExportVideo { options, .. }, edited_options) => FfmpegTaskType::export_video(edited_options.unwrap_or(*options)),
+        (_, Some(_)) => {
+            warn!("Export options can only be changed when duplicating an export task");
+            return None;
+        }
+        (FfmpegTaskType::ExtractAudio { video_file_path, .. }, None) => FfmpegTaskType::extract_audio(video_file_path, None),
+        (FfmpegTaskType::ConcatVideos { options, .. }, None) => FfmpegTaskType::concat_videos(options),
+        (FfmpegTaskType::DownloadFfmpeg { .. }, None) => FfmpegTaskType::DownloadFfmpeg { result: None },
+    };
 
-                        let mut ffmpeg_command = FfmpegCommand::new_with_path(ffmpeg_path());
+    let task = FfmpegTask::new(task_type);
+    let new_task_id = task.id;
+    enqueue_ffmpeg_task(queue, task).await;
 
-                        ffmpeg_command.input(&video_file_path).arg("-y").args(maps.split_whitespace());
+    Some(new_task_id)
+}
 
-                        info!("Running ffmpeg task: {:?}", ffmpeg_command.print_command());
+/// Removes a task which is no longer queued or running. Returns `false` if there is no such task.
+pub async fn remove_ffmpeg_task(queue: &FfmpegTasksQueue, task_id: u64) -> bool {
+    let mut queue = queue.lock().await;
+    let Some((index, task)) = find_ffmpeg_task(&queue, task_id).await else {
+        return false;
+    };
 
-                        let mut ffmpeg_child = ffmpeg_command.spawn().unwrap();
+    if task.read().await.finished_at().is_none() {
+        return false;
+    }
 
-                        ffmpeg_child.iter().unwrap().for_each(|e| match e {
-                            FfmpegEvent::Log(LogLevel::Error | LogLevel::Fatal, e) => {
-                                error!("Ffmpeg: {e}")
-                            }
-                            FfmpegEvent::Log(_log_level, s) => {
-                                info!("Ffmpeg: {s}")
-                            }
-                            FfmpegEvent::Progress(p) => {
-                                handle_ffmpeg_progress(p, &ffmpeg_task_clone, info.duration);
-                            }
-                            _ => {}
-                        });
+    queue.remove(index);
+    emit_ffmpeg_tasks_removed(vec![task_id]);
 
-                        let exit_status = ffmpeg_child.wait();
-                        debug!("Ffmpeg exited with status: {:?}", exit_status);
+    true
+}
 
-                        let successful = exit_status.map(|s| s.success()).unwrap_or(false);
+/// Removes all tasks which are no longer queued or running, returns how many were removed
+pub async fn remove_finished_ffmpeg_tasks(queue: &FfmpegTasksQueue) -> usize {
+    let mut queue = queue.lock().await;
 
-                        if successful { Some(result) } else { None }
-                    } else {
-                        None
-                    }
-                })
-                .await
-                .ok()
-                .flatten();
+    let mut kept_tasks = Vec::with_capacity(queue.len());
+    let mut removed_task_ids = vec![];
+    for task in queue.drain(..) {
+        let task_guard = task.read().await;
+        if task_guard.finished_at().is_some() {
+            removed_task_ids.push(task_guard.id);
+        } else {
+            drop(task_guard);
+            kept_tasks.push(task);
+        }
+    }
+    *queue = kept_tasks;
 
-                let mut ffmpeg_task = ffmpeg_task.write().await;
-                if let Some(ffmpeg_result) = ffmpeg_result.clone() {
-                    ffmpeg_task.status = FfmpegTaskStatus::Finished;
-                    if let FfmpegTaskType::ExtractAudio { on_complete, result, .. } = &mut ffmpeg_task.task_type {
-                        *result = Some(ffmpeg_result.clone());
-                        if let Some(sender) = on_complete.take() {
-                            sender.send(ffmpeg_result).unwrap();
-                        }
-                    }
-                } else {
-                    ffmpeg_task.status = FfmpegTaskStatus::Failed;
-                }
-                drop(ffmpeg_task);
-            }
-            FfmpegTaskType::ExportVideo { options, .. } => {
-                let options = options.clone();
-                drop(ffmpeg_task_guard);
+    let removed = removed_task_ids.len();
+    emit_ffmpeg_tasks_removed(removed_task_ids);
 
-                let ffmpeg_result = tokio::task::spawn_blocking(move || {
-                    let app_handle = APP_HANDLE.get().unwrap();
-                    if !app_handle.asset_protocol_scope().is_allowed(&options.input_path) {
-                        return None;
-                    }
+    removed
+}
 
-                    let info = get_video_streams_info(&options.input_path);
-                    if let Some(info) = info {
-                        let input_video_codec = info.streams.first().map(|s| s.codec_name.clone());
-
-                        let gpu_acceleration = {
-                            match options.gpu_acceleration {
-                                Some(GpuAcceleration::Nvidia) => {
-                                    let nvidia_gpu_args = "-hwaccel cuda -hwaccel_output_format cuda";
-                                    let gpu_scale_arg = "scale_cuda";
-
-                                    match &input_video_codec.as_deref() {
-                                        Some("h264") => Some((nvidia_gpu_args, "h264_cuvid", gpu_scale_arg)),
-                                        Some("hevc") => Some((nvidia_gpu_args, "hevc_cuvid", gpu_scale_arg)),
-                                        Some("av1") => Some((nvidia_gpu_args, "av1_cuvid", gpu_scale_arg)),
-                                        _ => None,
-                                    }
-                                }
-                                None => None,
-                            }
-                        };
-
-                        let scale = if let Some(resolution) = options.resolution {
-                            let scale_filter = gpu_acceleration.map(|(_, _, scale_filter)| scale_filter).unwrap_or("scale");
-                            Cow::Owned(format!(",{}={}", scale_filter, resolution))
-                        } else {
-                            Cow::Borrowed("")
-                        };
-
-                        let video_filter = format!("[0:v]setpts=PTS-STARTPTS{}[v]", scale);
-
-                        let audio_filter = if !options.active_audio_streams.is_empty() {
-                            let audio_streams_trim = options
-                                .active_audio_streams
-                                .iter()
-                                .map(|stream| format!("[0:{}]volume={},asetpts=PTS-STARTPTS[a{}];", stream.index, stream.gain, stream.index))
-                                .collect::<Vec<_>>()
-                                .join("");
-
-                            let audio_streams = options
-                                .active_audio_streams
-                                .iter()
-                                .map(|stream| format!("[a{}]", stream.index))
-                                .collect::<Vec<_>>()
-                                .join("");
-
-                            format!(
-                                "{}{}amix=inputs={}[a]",
-                                audio_streams_trim,
-                                audio_streams,
-                                options.active_audio_streams.len()
-                            )
-                        } else {
-                            // Generate silence
-                            format!("aevalsrc=0:d={}[a]", options.end_time - options.start_time)
-                        };
-
-                        let mut ffmpeg_command = FfmpegCommand::new_with_path(ffmpeg_path());
-
-                        if let Some((args, codec, _)) = gpu_acceleration {
-                            ffmpeg_command.args(args.split_whitespace());
-                            ffmpeg_command.codec_video(codec);
-                        }
+/// Starts queued tasks in priority order while their kind has free concurrency slots
+pub async fn run_next_task(queue: MutexGuard<'_, Vec<Arc<RwLock<FfmpegTask>>>>) {
+    let limits = APP_HANDLE.get().unwrap().state::<FfmpegSchedulerState>().limits();
 
-                        ffmpeg_command.seek(options.start_time.to_string().as_str());
-                        ffmpeg_command.to(options.end_time.to_string().as_str());
+    let mut running: HashMap<FfmpegTaskSlot, usize> = HashMap::new();
+    let mut queued_tasks = vec![];
 
-                        ffmpeg_command
-                            .input(&options.input_path)
-                            .overwrite()
-                            .filter_complex(format!("{};{}", video_filter, audio_filter))
-                            //.filter_complex(format!("\"{}\"", audio_filter))
-                            .map("[v]")
-                            .map("[a]");
+    for task in queue.iter() {
+        let task_guard = task.read().await;
+        let slot = task_guard.task_type.slot();
+        match task_guard.status {
+            FfmpegTaskStatus::InProgress { .. } | FfmpegTaskStatus::Paused { .. } => *running.entry(slot).or_default() += 1,
+            FfmpegTaskStatus::Queued => queued_tasks.push((task_guard.priority, slot, task.clone())),
+            _ => {}
+        }
+    }
 
-                        if let Some(codec) = options.video_codec {
-                            ffmpeg_command.codec_video(codec);
-                        }
+    drop(queue);
 
-                        if let Some(bitrate) = options.bitrate {
-                            ffmpeg_command.args(vec!["-b:v", &bitrate]);
-                        }
+    // Stable sort keeps queue order of tasks with equal priority
+    queued_tasks.sort_by_key(|(priority, _, _)| Reverse(*priority));
 
-                        if let Some(frame_rate) = options.frame_rate {
-                            ffmpeg_command.arg("-r");
-                            ffmpeg_command.arg(frame_rate.to_string().as_str());
-                        }
+    let mut tasks_to_start = vec![];
 
-                        ffmpeg_command.preset("medium").output(&options.output_path);
+    if !running.contains_key(&FfmpegTaskSlot::Exclusive) {
+        for (_, slot, task) in queued_tasks {
+            if slot == FfmpegTaskSlot::Exclusive {
+                if running.values().all(|count| *count == 0) && tasks_to_start.is_empty() {
+                    tasks_to_start.push(task);
+                }
+                // Nothing queued after an exclusive task starts before it
+                break;
+            }
 
-                        info!("Running ffmpeg command: {:?}", ffmpeg_command.print_command());
+            let running_count = running.entry(slot).or_default();
+            if *running_count < limits.limit(slot) {
+                *running_count += 1;
+                tasks_to_start.push(task);
+            }
+        }
+    }
 
-                        let mut ffmpeg_child = ffmpeg_command.spawn().unwrap();
+    for task in tasks_to_start {
+        // Mark as started right away, so the next scheduling pass doesn't start it again
+        let mut task_guard = task.write().await;
+        task_guard.status = FfmpegTaskStatus::in_progress(0.0);
+        task_guard.timing.start();
+        drop(task_guard);
+        tokio::spawn(run_ffmpeg_task(task));
+    }
+}
 
-                        let child_std_in = ffmpeg_child.take_stdin().unwrap();
-                        async_runtime::spawn(handle_ffmpeg_stdin(child_std_in, ffmpeg_task_clone.clone()));
+fn get_stream_file_path(kind: &str, video_file_path: &str, stream_index: i32, format: &str) -> Result<String, FfmpegTaskError> {
+    let tmp_folder = std::env::temp_dir().join(APP_IDENTIFIER);
+    std::fs::create_dir_all(&tmp_folder)?;
+    let file_name = format!("{}_{}_{}.{}", kind, BASE64_STANDARD.encode(video_file_path), stream_index, format);
 
-                        ffmpeg_child.iter().unwrap().for_each(|e| match e {
-                            FfmpegEvent::Log(LogLevel::Error | LogLevel::Fatal, e) => {
-                                error!("Ffmpeg: {e}")
-                            }
-                            FfmpegEvent::Log(_log_level, s) => {
-                                info!("Ffmpeg: {s}")
-                            }
-                            FfmpegEvent::Progress(p) => {
-                                handle_ffmpeg_progress(p, &ffmpeg_task_clone, options.end_time - options.start_time);
-                            }
-                            _ => {}
-                        });
+    Ok(tmp_folder.join(file_name).to_string_lossy().to_string())
+}
 
-                        let exit_status = ffmpeg_child.wait();
-                        debug!("Ffmpeg exited with status: {:?}", exit_status);
+#[allow(clippy::manual_async_fn)] // Recursive async function (Send is not auto implements)
+fn run_ffmpeg_task(ffmpeg_task: Arc<RwLock<FfmpegTask>>) -> impl Future<Output = ()> + Send {
+    async move {
+        emit_ffmpeg_task_changed(&ffmpeg_task).await;
 
-                        let successful = exit_status.map(|s| s.success()).unwrap_or(false);
+        let started_at = SystemTime::now();
+        let task_type = ffmpeg_task.read().await.task_type.clone();
+        let output_path = task_type.output_path().map(str::to_string);
+        let ffmpeg_task_clone = ffmpeg_task.clone();
 
-                        if successful {
-                            let result = FfmpegExportVideoTaskResult {
-                                output_path: options.output_path.clone(),
-                            };
+        match task_type {
+            FfmpegTaskType::ExtractAudio { video_file_path, .. } => {
+                let ffmpeg_result = run_blocking(move || extract_audio(&video_file_path, &ffmpeg_task_clone)).await;
 
-                            Some(result)
-                        } else {
-                            None
+                let mut ffmpeg_task = ffmpeg_task.write().await;
+                if ffmpeg_task.status != FfmpegTaskStatus::Cancelled {
+                    match ffmpeg_result {
+                        Ok(ffmpeg_result) => {
+                            ffmpeg_task.status = FfmpegTaskStatus::Finished;
+                            if let FfmpegTaskType::ExtractAudio { on_complete, result, .. } = &mut ffmpeg_task.task_type {
+                                *result = Some(ffmpeg_result.clone());
+                                if let Some(sender) = on_complete.take()
+                                    && sender.send(ffmpeg_result).is_err()
+                                {
+                                    warn!("Audio extraction result receiver is dropped");
+                                }
+                            }
                         }
-                    } else {
-                        None
+                        Err(e) => fail_ffmpeg_task(&mut ffmpeg_task, e),
                     }
-                })
-                .await
-                .ok()
-                .flatten();
+                }
+                drop(ffmpeg_task);
+            }
+            FfmpegTaskType::ExportVideo { options, .. } => {
+                let ffmpeg_result = run_blocking(move || export_video(*options, &ffmpeg_task_clone)).await;
 
                 let mut ffmpeg_task = ffmpeg_task.write().await;
                 info!("Task finished: {:?}", ffmpeg_task);
                 if ffmpeg_task.status != FfmpegTaskStatus::Cancelled {
-                    if ffmpeg_result.is_some() {
-                        ffmpeg_task.status = FfmpegTaskStatus::Finished;
-                        if let FfmpegTaskType::ExportVideo { result, .. } = &mut ffmpeg_task.task_type {
-                            *result = ffmpeg_result.clone();
+                    match ffmpeg_result {
+                        Ok(ffmpeg_result) => {
+                            ffmpeg_task.status = FfmpegTaskStatus::Finished;
+                            if let FfmpegTaskType::ExportVideo { result, .. } = &mut ffmpeg_task.task_type {
+                                *result = Some(ffmpeg_result);
+                            }
                         }
-                    } else {
-                        ffmpeg_task.status = FfmpegTaskStatus::Failed;
+                        Err(e) => fail_ffmpeg_task(&mut ffmpeg_task, e),
                     }
                 }
                 drop(ffmpeg_task);
             }
-            FfmpegTaskType::DownloadFfmpeg { .. } => {
-                drop(ffmpeg_task_guard);
-                let ffmpeg_task_clone = ffmpeg_task.clone();
+            FfmpegTaskType::ConcatVideos { options, .. } => {
+                let ffmpeg_result = run_blocking(move || concat_videos(options, &ffmpeg_task_clone)).await;
 
-                let ffmpeg_result = tokio::task::spawn_blocking(move || {
-                    let ffmpeg_is_installed = ffmpeg_is_installed();
-
-                    info!("FFmpeg is installed: {} (ffmpeg path: {:?})", ffmpeg_is_installed, ffmpeg_path().to_str());
-
-                    if ffmpeg_is_installed {
-                        return Ok(true);
+                let mut ffmpeg_task = ffmpeg_task.write().await;
+                info!("Task finished: {:?}", ffmpeg_task);
+                if ffmpeg_task.status != FfmpegTaskStatus::Cancelled {
+                    match ffmpeg_result {
+                        Ok(ffmpeg_result) => {
+                            ffmpeg_task.status = FfmpegTaskStatus::Finished;
+                            if let FfmpegTaskType::ConcatVideos { result, .. } = &mut ffmpeg_task.task_type {
+                                *result = Some(ffmpeg_result);
+                            }
+                        }
+                        Err(e) => fail_ffmpeg_task(&mut ffmpeg_task, e),
                     }
-
-                    info!("Downloading ffmpeg...");
-
-                    download_with_progress(|progress| {
-                        let ffmpeg_task_clone = ffmpeg_task_clone.clone();
-                        tokio::spawn(async move {
-                            let mut ffmpeg_task = ffmpeg_task_clone.write().await;
-                            ffmpeg_task.status = FfmpegTaskStatus::InProgress { progress };
-                            drop(ffmpeg_task);
-                            emit_ffmpeg_queue_status().await;
-                            set_main_window_progress_bar(Some(progress));
-                        });
-                    })?;
-
-                    info!("Ffmpeg downloaded successfully! ({:?})", ffmpeg_path().to_str());
-
-                    Ok::<bool, anyhow::Error>(false)
-                })
-                .await
-                .map_err(anyhow::Error::msg)
-                .flatten();
+                }
+                drop(ffmpeg_task);
+            }
+            FfmpegTaskType::DownloadFfmpeg { .. } => {
+                let ffmpeg_result = run_blocking(move || download_ffmpeg(&ffmpeg_task_clone)).await;
 
                 let mut ffmpeg_task = ffmpeg_task.write().await;
-                if let Ok(already_installed) = ffmpeg_result {
-                    ffmpeg_task.status = FfmpegTaskStatus::Finished;
-                    if let FfmpegTaskType::DownloadFfmpeg { result, .. } = &mut ffmpeg_task.task_type {
-                        *result = Some(FfmpegDownloadTaskResult { already_installed })
+                if ffmpeg_task.status != FfmpegTaskStatus::Cancelled {
+                    match ffmpeg_result {
+                        Ok(already_installed) => {
+                            ffmpeg_task.status = FfmpegTaskStatus::Finished;
+                            if let FfmpegTaskType::DownloadFfmpeg { result, .. } = &mut ffmpeg_task.task_type {
+                                *result = Some(FfmpegDownloadTaskResult { already_installed })
+                            }
+                        }
+                        Err(e) => fail_ffmpeg_task(&mut ffmpeg_task, e),
                     }
-                } else if let Err(e) = ffmpeg_result {
-                    ffmpeg_task.status = FfmpegTaskStatus::Failed;
-                    error!("Failed to download ffmpeg: {e}");
                 }
                 drop(ffmpeg_task);
             }
         };
 
-        emit_ffmpeg_queue_status().await;
-        set_main_window_progress_bar(None);
+        if ffmpeg_task.read().await.status == FfmpegTaskStatus::Cancelled
+            && let Some(output_path) = output_path
+        {
+            remove_partial_output(&output_path, started_at).await;
+        }
+
+        let persistent = {
+            let mut ffmpeg_task = ffmpeg_task.write().await;
+            ffmpeg_task.finished_at = Some(unix_time_millis());
+            let elapsed = ffmpeg_task.timing.active_elapsed();
+            info!("Ffmpeg task ran for {:?} excluding pauses", elapsed);
+            ffmpeg_task.elapsed_millis = Some(elapsed.as_millis() as u64);
+            ffmpeg_task.is_persistent()
+        };
+
+        if persistent {
+            save_ffmpeg_queue().await;
+        }
+
+        emit_ffmpeg_task_changed(&ffmpeg_task).await;
 
         let app_handle = APP_HANDLE.get().unwrap();
         let queue = app_handle.state::<FfmpegTasksQueue>();
@@ -518,6 +860,219 @@ fn run_ffmpeg_task(ffmpeg_task: Arc<RwLock<FfmpegTask>>) -> impl Future<Output =
     }
 }
 
+/// Removes output of a cancelled task, unless it wasn't written since the task started,
+/// so an existing file at the output path is kept if ffmpeg hasn't got to it
+async fn remove_partial_output(output_path: &str, started_at: SystemTime) {
+    let written_by_task = match tokio::fs::metadata(output_path).await.and_then(|metadata| metadata.modified()) {
+        Ok(modified) => modified >= started_at,
+        Err(_) => false,
+    };
+
+    if !written_by_task {
+        return;
+    }
+
+    match tokio::fs::remove_file(output_path).await {
+        Ok(()) => info!("Removed partial output {output_path}"),
+        Err(e) => warn!("Failed to remove partial output {output_path}: {e}"),
+    }
+}
+
+/// Runs a blocking task runner, a panic inside of it fails the task instead of leaving it in progress
+async fn run_blocking<T: Send + 'static>(runner: impl FnOnce() -> Result<T, FfmpegTaskError> + Send + 'static) -> Result<T, FfmpegTaskError> {
+    tokio::task::spawn_blocking(runner)
+        .await
+        .map_err(|e| FfmpegTaskError::Internal(format!("Task runner panicked: {e}")))
+        .flatten()
+}
+
+/// Extracts audio streams the player can't switch to and text subtitles as WebVTT into the temp dir,
+/// where the integrated server serves them for preview
+fn extract_audio(video_file_path: &str, ffmpeg_task: &Arc<RwLock<FfmpegTask>>) -> Result<FfmpegAudioExtractTaskResult, FfmpegTaskError> {
+    let probe = probe_media_blocking(video_file_path)?;
+    let info = VideoAudioStreamsInfo::from(probe.as_ref());
+
+    struct AudioStreamMap<'a> {
+        index: i32,
+        path: String,
+        ffmpeg_map: &'a str,
+    }
+
+    let audio_streams = info
+        .audio_streams
+        .iter()
+        .skip(1)
+        .map(|steam| {
+            let (format, ffmpeg_map) = if let Some(index) = WEB_SUPPORTED_AUDIO_CODECS.iter().position(|c| c == &steam.codec_name) {
+                (WEB_SUPPORTED_AUDIO_CODECS_CONTAINERS[index], "-c:a copy")
+            } else {
+                ("m4a", "-c:a aac -b:a 192k")
+            };
+
+            Ok(AudioStreamMap {
+                index: steam.index,
+                path: get_stream_file_path("audio", video_file_path, steam.index, format)?,
+                ffmpeg_map,
+            })
+        })
+        .collect::<Result<Vec<_>, FfmpegTaskError>>()?;
+
+    let subtitle_streams = probe
+        .streams_of_kind(MediaStreamKind::Subtitle)
+        .filter(|stream| !stream.is_bitmap_subtitle())
+        .map(|stream| {
+            Ok(SubtitleStreamFilePath {
+                index: stream.index,
+                path: get_stream_file_path("subtitles", video_file_path, stream.index, "vtt")?,
+            })
+        })
+        .collect::<Result<Vec<_>, FfmpegTaskError>>()?;
+
+    let result = FfmpegAudioExtractTaskResult {
+        audio_streams: audio_streams
+            .iter()
+            .map(|s| AudioStreamFilePath {
+                path: s.path.clone(),
+                index: s.index,
+            })
+            .collect(),
+        subtitle_streams: subtitle_streams.clone(),
+    };
+
+    let maps = audio_streams
+        .iter()
+        .map(|s| format!("-map 0:{} {} {}", s.index, s.ffmpeg_map, s.path))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if maps.is_empty() && subtitle_streams.is_empty() {
+        return Ok(result);
+    }
+
+    let mut ffmpeg_command = FfmpegCommand::new_with_path(ffmpeg_path());
+
+    ffmpeg_command.input(video_file_path).arg("-y").args(maps.split_whitespace());
+
+    for stream in &subtitle_streams {
+        ffmpeg_command
+            .map(format!("0:{}", stream.index))
+            .args(["-c:s", "webvtt"])
+            .output(&stream.path);
+    }
+
+    if let Err(e) = run_ffmpeg_command(ffmpeg_command, ffmpeg_task, 0.0, info.duration, ProgressSpan::WHOLE) {
+        let paths = audio_streams.iter().map(|s| &s.path).chain(subtitle_streams.iter().map(|s| &s.path));
+        for path in paths {
+            if let Err(e) = std::fs::remove_file(path) {
+                debug!("Failed to remove partial stream file {}: {e}", path);
+            }
+        }
+        return Err(e);
+    }
+
+    Ok(result)
+}
+
+fn export_video(options: ExportOptions, ffmpeg_task: &Arc<RwLock<FfmpegTask>>) -> Result<FfmpegExportVideoTaskResult, FfmpegTaskError> {
+    let app_handle = APP_HANDLE.get().unwrap();
+    if !app_handle.asset_protocol_scope().is_allowed(&options.input_path) {
+        return Err(FfmpegTaskError::InputNotAllowed(options.input_path.clone()));
+    }
+    if let Some(BurnInSubtitles::File { path }) = &options.burn_in_subtitles
+        && !app_handle.asset_protocol_scope().is_allowed(path)
+    {
+        return Err(FfmpegTaskError::InputNotAllowed(path.clone()));
+    }
+
+    let mut options = options;
+    if !options.removed_segments.is_empty() {
+        let duration = probe_media_blocking(&options.input_path)?
+            .format
+            .duration
+            .ok_or_else(|| FfmpegTaskError::Prepare(format!("Duration of {} is unknown", options.input_path)))?;
+        if !options.resolve_removed_segments(duration) {
+            return Err(FfmpegTaskError::InvalidOptions(
+                "Nothing is left to export after removing segments".to_string(),
+            ));
+        }
+    }
+
+    let segments = match (options.mode, options.target_size_bytes) {
+        (ExportMode::Reencode, Some(target_size_bytes)) => run_target_size_export(&options, target_size_bytes, ffmpeg_task)?,
+        (mode, target_size_bytes) => {
+            if target_size_bytes.is_some() {
+                warn!("Target size is ignored in {:?} mode", mode);
+            }
+
+            let export_plan = match mode {
+                ExportMode::Reencode => reencode_export_plan(&options),
+                ExportMode::StreamCopy => stream_copy_export_plan(&options),
+                ExportMode::SmartRender => smart_render_export_plan(&options),
+            }?;
+
+            run_export_plan(export_plan, ffmpeg_task, ProgressSpan::WHOLE)?
+        }
+    };
+
+    Ok(FfmpegExportVideoTaskResult {
+        output_path: options.output_path.clone(),
+        start_time: segments.first().map(|s| s.start).unwrap_or(options.start_time),
+        end_time: segments.last().map(|s| s.end).unwrap_or(options.end_time),
+        segments,
+    })
+}
+
+fn concat_videos(options: ConcatOptions, ffmpeg_task: &Arc<RwLock<FfmpegTask>>) -> Result<FfmpegConcatVideosTaskResult, FfmpegTaskError> {
+    let app_handle = APP_HANDLE.get().unwrap();
+    if let Some(clip) = options
+        .clips
+        .iter()
+        .find(|clip| !app_handle.asset_protocol_scope().is_allowed(&clip.input_path))
+    {
+        return Err(FfmpegTaskError::InputNotAllowed(clip.input_path.clone()));
+    }
+
+    let export_plan = concat_export_plan(&options)?;
+    run_export_plan(export_plan, ffmpeg_task, ProgressSpan::WHOLE)?;
+
+    Ok(FfmpegConcatVideosTaskResult {
+        output_path: options.output_path.clone(),
+    })
+}
+
+/// Downloads ffmpeg unless it is installed, returns whether it was already installed
+fn download_ffmpeg(ffmpeg_task: &Arc<RwLock<FfmpegTask>>) -> Result<bool, FfmpegTaskError> {
+    let ffmpeg_is_installed = ffmpeg_is_installed();
+
+    info!("FFmpeg is installed: {} (ffmpeg path: {:?})", ffmpeg_is_installed, ffmpeg_path().to_str());
+
+    if ffmpeg_is_installed {
+        return Ok(true);
+    }
+
+    info!("Downloading ffmpeg...");
+
+    download_with_progress(
+        |progress| {
+            let ffmpeg_task = ffmpeg_task.clone();
+            async_runtime::spawn(async move {
+                let mut ffmpeg_task_guard = ffmpeg_task.write().await;
+                if matches!(ffmpeg_task_guard.status, FfmpegTaskStatus::InProgress { .. }) {
+                    ffmpeg_task_guard.status = FfmpegTaskStatus::in_progress(progress);
+                }
+                drop(ffmpeg_task_guard);
+                emit_ffmpeg_task_progress(&ffmpeg_task).await;
+            });
+        },
+        || ffmpeg_task.blocking_read().status == FfmpegTaskStatus::Cancelled,
+    )
+    .map_err(FfmpegTaskError::Download)?;
+
+    info!("Ffmpeg downloaded successfully! ({:?})", ffmpeg_path().to_str());
+
+    Ok(false)
+}
+
 pub async fn enqueue_extract_audio_task(queue: &FfmpegTasksQueue, path: String, on_complete: Option<oneshot::Sender<FfmpegAudioExtractTaskResult>>) {
     enqueue_ffmpeg_task(queue, FfmpegTask::new(FfmpegTaskType::extract_audio(path, on_complete))).await;
 }
@@ -526,91 +1081,554 @@ pub async fn enqueue_export_video_task(queue: &FfmpegTasksQueue, options: Export
     enqueue_ffmpeg_task(queue, FfmpegTask::new(FfmpegTaskType::export_video(options))).await;
 }
 
+pub async fn enqueue_concat_videos_task(queue: &FfmpegTasksQueue, options: ConcatOptions) {
+    enqueue_ffmpeg_task(queue, FfmpegTask::new(FfmpegTaskType::concat_videos(options))).await;
+}
+
 pub async fn enqueue_download_ffmpeg_task(queue: &FfmpegTasksQueue) {
     enqueue_ffmpeg_task(queue, FfmpegTask::new(FfmpegTaskType::DownloadFfmpeg { result: None })).await;
 }
 
-pub async fn emit_ffmpeg_queue_status() {
+/// Clones of all tasks in queue order
+pub async fn ffmpeg_queue_snapshot(queue: &FfmpegTasksQueue) -> Vec<FfmpegTask> {
+    let queue = queue.lock().await;
+    futures::future::join_all(queue.iter().map(|task| async { task.read().await.clone() })).await
+}
+
+fn emit_ffmpeg_task_update(update: FfmpegTaskUpdate) {
     let app_handle = APP_HANDLE.get().unwrap();
-    let queue = app_handle.state::<FfmpegTasksQueue>();
-    let queue_lock = queue.lock().await;
-    let tasks = futures::future::join_all(queue_lock.iter().map(|task| async { task.read().await.clone() })).await;
-    drop(queue_lock);
-    app_handle.emit("ffmpeg-queue", tasks).unwrap();
+    if let Err(e) = app_handle.emit(FFMPEG_TASK_UPDATE_EVENT, update) {
+        warn!("Failed to emit ffmpeg task update: {e}");
+    }
+}
+
+/// Sends the whole task to the frontend, the queue must not be locked by the caller
+pub async fn emit_ffmpeg_task_changed(ffmpeg_task: &Arc<RwLock<FfmpegTask>>) {
+    let task = ffmpeg_task.read().await.clone();
+    emit_ffmpeg_task_update(FfmpegTaskUpdate::Changed { task });
+    update_main_window_progress_bar().await;
+}
+
+/// Sends status of a running task unless it was sent less than [`FFMPEG_PROGRESS_EMIT_INTERVAL`] ago
+async fn emit_ffmpeg_task_progress(ffmpeg_task: &Arc<RwLock<FfmpegTask>>) {
+    let mut task = ffmpeg_task.write().await;
+    if task
+        .last_progress_emit
+        .is_some_and(|emitted_at| emitted_at.elapsed() < FFMPEG_PROGRESS_EMIT_INTERVAL)
+    {
+        return;
+    }
+    task.last_progress_emit = Some(Instant::now());
+
+    let update = FfmpegTaskUpdate::Progress {
+        task_id: task.id,
+        status: task.status.clone(),
+    };
+    drop(task);
+
+    emit_ffmpeg_task_update(update);
+    update_main_window_progress_bar().await;
+}
+
+pub fn emit_ffmpeg_tasks_removed(task_ids: Vec<u64>) {
+    if !task_ids.is_empty() {
+        emit_ffmpeg_task_update(FfmpegTaskUpdate::Removed { task_ids });
+    }
+}
+
+async fn emit_ffmpeg_queue_order(queue: &[Arc<RwLock<FfmpegTask>>]) {
+    let task_ids = futures::future::join_all(queue.iter().map(|task| async { task.read().await.id })).await;
+    emit_ffmpeg_task_update(FfmpegTaskUpdate::Reordered { task_ids });
 }
 
-async fn handle_ffmpeg_stdin(mut stdin: ChildStdin, ffmpeg_task: Arc<RwLock<FfmpegTask>>) {
-    let (tx, mut rx) = mpsc::channel::<String>(100);
+async fn update_main_window_progress_bar() {
+    let app_handle = APP_HANDLE.get().unwrap();
+    let queue = app_handle.state::<FfmpegTasksQueue>();
+    let queue = queue.lock().await;
 
-    ffmpeg_task.write().await.ffmpeg_stdin.replace(tx);
+    let mut progresses = vec![];
+    for task in queue.iter() {
+        if let FfmpegTaskStatus::InProgress { progress, .. } | FfmpegTaskStatus::Paused { progress } = task.read().await.status {
+            progresses.push(progress);
+        }
+    }
+    drop(queue);
 
+    set_main_window_progress_bar(aggregate_progress(&progresses));
+}
+
+/// Writes messages to ffmpeg stdin until the sender stored in the task is dropped
+async fn handle_ffmpeg_stdin(mut stdin: ChildStdin, mut rx: mpsc::Receiver<String>) {
     while let Some(s) = rx.recv().await {
         debug!("Received ffmpeg stdin: {s}");
-        stdin.write_all(s.as_bytes()).unwrap();
+        if let Err(e) = stdin.write_all(s.as_bytes()).and_then(|_| stdin.flush()) {
+            // ffmpeg has most likely exited already
+            warn!("Failed to write to ffmpeg stdin: {e}");
+            break;
+        }
+    }
+}
+
+fn fail_ffmpeg_task(ffmpeg_task: &mut FfmpegTask, e: FfmpegTaskError) {
+    error!("Ffmpeg task failed: {e}");
+    ffmpeg_task.status = FfmpegTaskStatus::Failed { reason: e.into() };
+}
+
+/// Cancels a queued or running task, returns `false` if the task has already finished.
+/// Running ffmpeg is asked to quit with `q` and killed if it hasn't exited in [`FFMPEG_CANCEL_TIMEOUT`].
+pub async fn cancel_ffmpeg_task(ffmpeg_task: &Arc<RwLock<FfmpegTask>>) -> bool {
+    let mut ffmpeg_task_guard = ffmpeg_task.write().await;
+    match ffmpeg_task_guard.status {
+        FfmpegTaskStatus::Queued => {
+            ffmpeg_task_guard.status = FfmpegTaskStatus::Cancelled;
+            ffmpeg_task_guard.finished_at = Some(unix_time_millis());
+            return true;
+        }
+        FfmpegTaskStatus::InProgress { .. } | FfmpegTaskStatus::Paused { .. } => {}
+        _ => return false,
+    }
+
+    // Suspended ffmpeg can't read `q`
+    if matches!(ffmpeg_task_guard.status, FfmpegTaskStatus::Paused { .. })
+        && let Some(process) = &ffmpeg_task_guard.ffmpeg_process
+        && let Err(e) = resume_process(process.pid)
+    {
+        warn!("Failed to resume ffmpeg before cancelling: {e}");
+    }
+
+    // Runners check the status between steps, so tasks without a running process stop on their own
+    ffmpeg_task_guard.status = FfmpegTaskStatus::Cancelled;
+
+    if let Some(ffmpeg_stdin) = &ffmpeg_task_guard.ffmpeg_stdin {
+        match ffmpeg_stdin.send("q".to_string()).await {
+            Ok(()) => debug!("Sent q to ffmpeg stdin"),
+            Err(e) => warn!("Failed to send q to ffmpeg stdin: {e}"),
+        }
+    }
+
+    if let Some(process) = ffmpeg_task_guard.ffmpeg_process.clone() {
+        tokio::spawn(kill_ffmpeg_after_timeout(ffmpeg_task.clone(), process));
+    }
+
+    true
+}
+
+/// Kills ffmpeg of a cancelled task if it is still running after [`FFMPEG_CANCEL_TIMEOUT`]
+async fn kill_ffmpeg_after_timeout(ffmpeg_task: Arc<RwLock<FfmpegTask>>, process: FfmpegProcess) {
+    tokio::time::sleep(FFMPEG_CANCEL_TIMEOUT).await;
+
+    let ffmpeg_task = ffmpeg_task.read().await;
+    let still_running = ffmpeg_task
+        .ffmpeg_process
+        .as_ref()
+        .is_some_and(|running| Arc::ptr_eq(&running.child, &process.child));
+    if !still_running {
+        return;
+    }
+
+    warn!("Ffmpeg hasn't quit in {:?} after cancelling, killing it", FFMPEG_CANCEL_TIMEOUT);
+    // Locked child is being awaited, so it is exiting already
+    if let Ok(mut child) = process.child.try_lock()
+        && let Err(e) = child.kill()
+    {
+        warn!("Failed to kill ffmpeg: {e}");
+    }
+}
+
+/// Suspends ffmpeg process of the running task
+pub async fn pause_ffmpeg_task(ffmpeg_task: &Arc<RwLock<FfmpegTask>>) -> Result<(), String> {
+    let mut ffmpeg_task = ffmpeg_task.write().await;
+    let FfmpegTaskStatus::InProgress { progress, .. } = ffmpeg_task.status else {
+        return Err("Task is not in progress".to_string());
+    };
+
+    // Checked up front, a task paused between steps would otherwise show as paused while its next step runs
+    if !SUSPEND_SUPPORTED {
+        return Err("Pausing ffmpeg is not supported on this platform".to_string());
+    }
+
+    // Without a process the task is between steps, the next step starts suspended
+    if let Some(process) = &ffmpeg_task.ffmpeg_process {
+        suspend_process(process.pid).map_err(|e| format!("Failed to pause ffmpeg: {e}"))?;
+    } else if matches!(ffmpeg_task.task_type, FfmpegTaskType::DownloadFfmpeg { .. }) {
+        return Err("FFmpeg download can't be paused".to_string());
     }
+
+    ffmpeg_task.status = FfmpegTaskStatus::Paused { progress };
+    ffmpeg_task.timing.pause();
+
+    Ok(())
 }
 
-#[allow(clippy::collapsible_if)]
-pub async fn cancel_ffmpeg_task(ffmpeg_task: &Arc<RwLock<FfmpegTask>>) {
+pub async fn resume_ffmpeg_task(ffmpeg_task: &Arc<RwLock<FfmpegTask>>) -> Result<(), String> {
     let mut ffmpeg_task = ffmpeg_task.write().await;
-    if let Some(ffmpeg_stdin) = &ffmpeg_task.ffmpeg_stdin {
-        if ffmpeg_stdin.send("q".to_string()).await.is_ok() {
-            debug!("Sent q to ffmpeg stdin");
-            ffmpeg_task.status = FfmpegTaskStatus::Cancelled;
+    let FfmpegTaskStatus::Paused { progress } = ffmpeg_task.status else {
+        return Err("Task is not paused".to_string());
+    };
+
+    if let Some(process) = &ffmpeg_task.ffmpeg_process {
+        resume_process(process.pid).map_err(|e| format!("Failed to resume ffmpeg: {e}"))?;
+    }
+
+    ffmpeg_task.status = FfmpegTaskStatus::in_progress(progress);
+    ffmpeg_task.timing.resume();
+
+    Ok(())
+}
+
+/// Two-pass encodes the export until the output fits into `target_size_bytes`,
+/// lowering the bitrate after each overshoot. Retries continue the progress of the previous attempts.
+fn run_target_size_export(
+    options: &ExportOptions,
+    target_size_bytes: u64,
+    ffmpeg_task: &Arc<RwLock<FfmpegTask>>,
+) -> Result<Vec<TimeRange>, FfmpegTaskError> {
+    let mut video_bitrate_kbps = target_size_video_bitrate_kbps(options, target_size_bytes)?;
+    let mut output_size = 0;
+    let mut progress_span = ProgressSpan::WHOLE;
+
+    for attempt in 1..=TARGET_SIZE_MAX_ATTEMPTS {
+        info!("Target size export attempt {attempt} with {video_bitrate_kbps} kbps video bitrate");
+
+        if attempt < TARGET_SIZE_MAX_ATTEMPTS {
+            progress_span.end = progress_span.at(TARGET_SIZE_ATTEMPT_PROGRESS_SHARE);
+        } else {
+            progress_span.end = 1.0;
+        }
+        if attempt > 1 {
+            ffmpeg_task.blocking_write().timing.restart_speed_samples();
         }
+
+        let export_plan = two_pass_export_plan(options, video_bitrate_kbps)?;
+        let segments = run_export_plan(export_plan, ffmpeg_task, progress_span)?;
+        progress_span.start = progress_span.end;
+
+        output_size = std::fs::metadata(&options.output_path)?.len();
+        if output_size <= target_size_bytes {
+            return Ok(segments);
+        }
+
+        warn!("Export overshot target size: {output_size} > {target_size_bytes} bytes");
+        video_bitrate_kbps *= target_size_bytes as f64 / output_size as f64 * TARGET_SIZE_RETRY_BITRATE_FACTOR;
+    }
+
+    error!("Failed to fit export into {target_size_bytes} bytes after {TARGET_SIZE_MAX_ATTEMPTS} attempts");
+    Err(FfmpegTaskError::OutputTooLarge {
+        size: output_size,
+        target_size: target_size_bytes,
+    })
+}
+
+/// Part of the task progress a run moves through, from `start` when it begins to `end` when it finishes
+#[derive(Debug, Clone, Copy)]
+struct ProgressSpan {
+    start: f64,
+    end: f64,
+}
+
+impl ProgressSpan {
+    const WHOLE: ProgressSpan = ProgressSpan { start: 0.0, end: 1.0 };
+
+    /// Task progress once `fraction` of the run is done
+    fn at(&self, fraction: f64) -> f64 {
+        self.start + (self.end - self.start) * fraction
     }
 }
 
-fn handle_ffmpeg_progress(p: FfmpegProgress, ffmpeg_task: &Arc<RwLock<FfmpegTask>>, total_duration: f64) {
+/// Runs all steps of the plan one by one and removes its intermediate files, moving the task progress through `progress_span`.
+/// Returns cut ranges used by the plan if all steps succeeded.
+fn run_export_plan(
+    export_plan: ExportPlan,
+    ffmpeg_task: &Arc<RwLock<FfmpegTask>>,
+    progress_span: ProgressSpan,
+) -> Result<Vec<TimeRange>, FfmpegTaskError> {
+    let total_duration = export_plan.total_duration();
+    let mut progress_offset = 0.0;
+    let mut result = export_plan
+        .temp_file_contents
+        .iter()
+        .try_for_each(|(path, contents)| std::fs::write(path, contents))
+        .map_err(FfmpegTaskError::from);
+
+    for step in export_plan.steps {
+        if result.is_err() {
+            break;
+        }
+
+        if ffmpeg_task.blocking_read().status == FfmpegTaskStatus::Cancelled {
+            result = Err(FfmpegTaskError::Cancelled);
+            break;
+        }
+
+        result = run_ffmpeg_command(step.command, ffmpeg_task, progress_offset, total_duration, progress_span);
+        progress_offset += step.duration;
+    }
+
+    for temp_file in &export_plan.temp_files {
+        if let Err(e) = std::fs::remove_file(temp_file) {
+            debug!("Failed to remove temp file {:?}: {e}", temp_file);
+        }
+    }
+    for prefix in &export_plan.temp_file_prefixes {
+        remove_files_with_prefix(prefix);
+    }
+
+    result.map(|_| export_plan.segments)
+}
+
+/// Removes the files in the folder of `prefix` whose names start with its file name
+fn remove_files_with_prefix(prefix: &Path) {
+    let (Some(folder), Some(name_prefix)) = (prefix.parent(), prefix.file_name()) else {
+        return;
+    };
+    let name_prefix = name_prefix.to_string_lossy();
+
+    let entries = match std::fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("Failed to list temp files in {:?}: {e}", folder);
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(name_prefix.as_ref())
+            && let Err(e) = std::fs::remove_file(entry.path())
+        {
+            debug!("Failed to remove temp file {:?}: {e}", entry.path());
+        }
+    }
+}
+
+/// Runs ffmpeg command until it exits, its progress is reported as `progress_offset`
+/// seconds already done out of `total_duration` seconds of `progress_span`
+fn run_ffmpeg_command(
+    mut ffmpeg_command: FfmpegCommand,
+    ffmpeg_task: &Arc<RwLock<FfmpegTask>>,
+    progress_offset: f64,
+    total_duration: f64,
+    progress_span: ProgressSpan,
+) -> Result<(), FfmpegTaskError> {
+    let command_line = format!("{:?}", ffmpeg_command.as_inner());
+    info!("Running ffmpeg command: {command_line}");
+
+    let log = ffmpeg_task.blocking_read().log();
+    log.lock().unwrap().start_run(command_line);
+
+    let mut ffmpeg_child = ffmpeg_command.spawn().map_err(|e| {
+        log.lock().unwrap().finish_run(format!("failed to start: {e}"));
+        match e.kind() {
+            std::io::ErrorKind::NotFound => FfmpegTaskError::FfmpegNotInstalled,
+            _ => FfmpegTaskError::Spawn(e),
+        }
+    })?;
+
+    let Some(child_std_in) = ffmpeg_child.take_stdin() else {
+        let _ = ffmpeg_child.kill();
+        log.lock().unwrap().finish_run("killed, stdin is not available".to_string());
+        return Err(FfmpegTaskError::Stdin);
+    };
+
+    let ffmpeg_iter = match ffmpeg_child.iter() {
+        Ok(ffmpeg_iter) => ffmpeg_iter,
+        Err(e) => {
+            let _ = ffmpeg_child.kill();
+            log.lock().unwrap().finish_run(format!("killed, output is not available: {e}"));
+            return Err(FfmpegTaskError::Output(e));
+        }
+    };
+
+    let process = FfmpegProcess {
+        pid: ffmpeg_child.as_inner().id(),
+        child: Arc::new(std::sync::Mutex::new(ffmpeg_child)),
+    };
+
+    {
+        let mut ffmpeg_task = ffmpeg_task.blocking_write();
+        match ffmpeg_task.status {
+            // Task was cancelled while the process was starting
+            FfmpegTaskStatus::Cancelled => {
+                drop(ffmpeg_task);
+                let mut child = process.child.lock().unwrap();
+                let _ = child.kill();
+                let _ = child.wait();
+                log.lock().unwrap().finish_run("killed, task was cancelled".to_string());
+                return Err(FfmpegTaskError::Cancelled);
+            }
+            // Task was paused between steps of its export plan
+            FfmpegTaskStatus::Paused { .. } => {
+                if let Err(e) = suspend_process(process.pid) {
+                    drop(ffmpeg_task);
+                    let mut child = process.child.lock().unwrap();
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    log.lock().unwrap().finish_run(format!("killed, failed to pause: {e}"));
+                    return Err(FfmpegTaskError::Spawn(e));
+                }
+            }
+            _ => {}
+        }
+
+        let (tx, rx) = mpsc::channel::<String>(100);
+        ffmpeg_task.ffmpeg_stdin = Some(tx);
+        ffmpeg_task.ffmpeg_process = Some(process.clone());
+        async_runtime::spawn(handle_ffmpeg_stdin(child_std_in, rx));
+    }
+
+    let mut errors = VecDeque::with_capacity(FFMPEG_ERROR_LINES);
+
+    // Regular output only goes to the task log, so it doesn't push everything else out of the app logs
+    ffmpeg_iter.for_each(|e| match e {
+        FfmpegEvent::Log(log_level @ (LogLevel::Error | LogLevel::Fatal), e) => {
+            error!("Ffmpeg: {e}");
+            log.lock().unwrap().push_line(&log_level, &e);
+            if errors.len() == FFMPEG_ERROR_LINES {
+                errors.pop_front();
+            }
+            errors.push_back(e);
+        }
+        FfmpegEvent::Log(log_level, s) => {
+            trace!("Ffmpeg: {s}");
+            log.lock().unwrap().push_line(&log_level, &s);
+        }
+        FfmpegEvent::Progress(p) => {
+            handle_ffmpeg_progress(p, ffmpeg_task, progress_offset, total_duration, progress_span);
+        }
+        _ => {}
+    });
+
+    // Cleared before the process is reaped, so its pid can't be reused by the time it is signaled
+    let cancelled = {
+        let mut ffmpeg_task = ffmpeg_task.blocking_write();
+        ffmpeg_task.ffmpeg_process = None;
+        ffmpeg_task.ffmpeg_stdin = None;
+        ffmpeg_task.status == FfmpegTaskStatus::Cancelled
+    };
+
+    let exit_status = process.child.lock().unwrap().wait()?;
+    debug!("Ffmpeg exited with status: {:?}", exit_status);
+    log.lock().unwrap().finish_run(exit_status.to_string());
+
+    if cancelled {
+        return Err(FfmpegTaskError::Cancelled);
+    }
+
+    if !exit_status.success() {
+        return Err(FfmpegTaskError::Ffmpeg {
+            exit_status: exit_status.to_string(),
+            errors: errors.into(),
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_ffmpeg_progress(
+    p: FfmpegProgress,
+    ffmpeg_task: &Arc<RwLock<FfmpegTask>>,
+    progress_offset: f64,
+    total_duration: f64,
+    progress_span: ProgressSpan,
+) {
     let ffmpeg_task_clone = ffmpeg_task.clone();
-    debug!("FFmpeg progress event: {:?}", p);
+    trace!("FFmpeg progress event: {:?}", p);
     tokio::spawn(async move {
         let mut ffmpeg_task = ffmpeg_task_clone.write().await;
 
-        let progress = if ffmpeg_task.status != FfmpegTaskStatus::Cancelled {
-            let progress = FfmpegTimeDuration::from_str(&p.time)
-                .map(FfmpegTimeDuration::as_seconds)
-                .unwrap_or_default()
-                / total_duration;
-            ffmpeg_task.status = FfmpegTaskStatus::InProgress { progress };
-            Some(progress)
-        } else {
-            None
-        };
+        // Late events must not override cancelled or paused status
+        if let FfmpegTaskStatus::InProgress { progress, .. } = ffmpeg_task.status {
+            let processed_seconds = progress_offset
+                + FfmpegTimeDuration::from_str(&p.time)
+                    .map(FfmpegTimeDuration::as_seconds)
+                    .unwrap_or_default();
+            let speed = ffmpeg_task.timing.update_speed(processed_seconds);
+
+            // Without a known duration only the statistics are updated
+            let known_duration = total_duration > 0.0;
+
+            ffmpeg_task.status = FfmpegTaskStatus::InProgress {
+                progress: if known_duration {
+                    progress_span.at((processed_seconds / total_duration).clamp(0.0, 1.0))
+                } else {
+                    progress
+                },
+                speed,
+                fps: positive(p.fps),
+                bitrate_kbps: positive(p.bitrate_kbps),
+                output_size: (p.size_kb > 0).then(|| p.size_kb as u64 * 1024),
+                eta_seconds: speed
+                    .filter(|speed| *speed > 0.0 && known_duration)
+                    .map(|speed| (total_duration - processed_seconds).max(0.0) / speed),
+            };
+        }
 
         drop(ffmpeg_task);
-        emit_ffmpeg_queue_status().await;
-        set_main_window_progress_bar(progress);
-        // println!("ffmpeg progress: {}%", progress * 100.0);
+        emit_ffmpeg_task_progress(&ffmpeg_task_clone).await;
     });
 }
 
+/// ffmpeg reports unknown statistics as zero or negative values
+fn positive(value: f32) -> Option<f64> {
+    (value > 0.0).then_some(value as f64)
+}
+
+/// Mean progress of all running tasks, `None` if nothing is running
+fn aggregate_progress(progresses: &[f64]) -> Option<f64> {
+    if progresses.is_empty() {
+        return None;
+    }
+
+    Some(progresses.iter().sum::<f64>() / progresses.len() as f64)
+}
+
 fn set_main_window_progress_bar(progress: Option<f64>) {
     let app_handle = APP_HANDLE.get().unwrap();
-    let main_window = app_handle.get_webview_window("main").unwrap();
-
-    if let Some(progress) = progress {
-        if progress != 0.0 {
-            main_window
-                .set_progress_bar(ProgressBarState {
-                    status: Some(ProgressBarStatus::Normal),
-                    progress: Some((progress * 100.0) as u64),
-                })
-                .unwrap();
-        } else {
-            main_window
-                .set_progress_bar(ProgressBarState {
-                    status: Some(ProgressBarStatus::Indeterminate),
-                    progress: None,
-                })
-                .unwrap();
-        }
-    } else {
-        main_window
-            .set_progress_bar(ProgressBarState {
-                status: Some(ProgressBarStatus::None),
-                progress: None,
-            })
-            .unwrap();
+    let Some(main_window) = app_handle.get_webview_window("main") else {
+        return;
+    };
+
+    let progress_bar_state = match progress {
+        Some(progress) if progress != 0.0 => ProgressBarState {
+            status: Some(ProgressBarStatus::Normal),
+            progress: Some((progress * 100.0) as u64),
+        },
+        Some(_) => ProgressBarState {
+            status: Some(ProgressBarStatus::Indeterminate),
+            progress: None,
+        },
+        None => ProgressBarState {
+            status: Some(ProgressBarStatus::None),
+            progress: None,
+        },
+    };
+
+    if let Err(e) = main_window.set_progress_bar(progress_bar_state) {
+        warn!("Failed to set progress bar: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queued_task_moves_take_over_the_neighbor_priority() {
+        // Task at index 0 is running, the one at index 2 has a higher priority and starts before 1 and 3
+        let order = [(2, 5), (1, 0), (3, 0)];
+
+        assert_eq!(queued_task_move(&order, 3, FfmpegTaskMove::Up), Some((1, 0)));
+        assert_eq!(queued_task_move(&order, 1, FfmpegTaskMove::Down), Some((3, 0)));
+        assert_eq!(queued_task_move(&order, 1, FfmpegTaskMove::Front), Some((1, 5)));
+        assert_eq!(queued_task_move(&order, 3, FfmpegTaskMove::Front), Some((2, 5)));
+    }
+
+    #[test]
+    fn queued_task_moves_stop_at_the_ends() {
+        let order = [(2, 5), (1, 0), (3, 0)];
+
+        assert_eq!(queued_task_move(&order, 2, FfmpegTaskMove::Up), None);
+        assert_eq!(queued_task_move(&order, 2, FfmpegTaskMove::Front), None);
+        assert_eq!(queued_task_move(&order, 3, FfmpegTaskMove::Down), None);
+        assert_eq!(queued_task_move(&order, 0, FfmpegTaskMove::Up), None);
     }
 }
@@ -0,0 +1,186 @@
+use crate::ffmpeg_export_command::{ChapterMarker, ExportChapters, ExportOptions, TimeRange};
+use crate::ffprobe::{MediaChapter, probe_media_blocking};
+use log::warn;
+
+/// Chapter pieces shorter than this are left out of the output
+const MIN_CHAPTER_DURATION: f64 = 0.001;
+
+/// Chapters of the export output, rebased from the input timeline to the output joined from `segments`
+pub fn export_chapters(options: &ExportOptions, segments: &[TimeRange], crossfade: f64) -> Vec<MediaChapter> {
+    let chapters = match &options.chapters {
+        ExportChapters::Remove => return vec![],
+        ExportChapters::Source => match probe_media_blocking(&options.input_path) {
+            Ok(probe) => probe.chapters.clone(),
+            Err(e) => {
+                warn!("Chapters are not exported: {e}");
+                return vec![];
+            }
+        },
+        ExportChapters::Markers { markers } => {
+            // The last chapter lasts until the end of the input
+            let end = probe_media_blocking(&options.input_path)
+                .ok()
+                .and_then(|probe| probe.format.duration)
+                .or_else(|| segments.last().map(|s| s.end))
+                .unwrap_or_default();
+            markers_to_chapters(markers, end)
+        }
+    };
+
+    rebase_chapters(&chapters, segments, crossfade)
+}
+
+/// Chapters from each marker to the next one, the last one ends at `end`
+fn markers_to_chapters(markers: &[ChapterMarker], end: f64) -> Vec<MediaChapter> {
+    let mut markers = markers.iter().filter(|m| m.time >= 0.0 && m.time < end).collect::<Vec<_>>();
+    markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+    markers.dedup_by(|a, b| a.time == b.time);
+
+    markers
+        .iter()
+        .enumerate()
+        .map(|(i, marker)| MediaChapter {
+            start: marker.time,
+            end: markers.get(i + 1).map(|next| next.time).unwrap_or(end),
+            title: marker.title.clone().filter(|title| !title.is_empty()),
+        })
+        .collect()
+}
+
+/// Clips `chapters` to `segments` and moves them to the output timeline where the segments are joined
+/// with `crossfade` overlaps. Chapters spanning a cut stay one chapter, chapters cut out entirely are dropped.
+fn rebase_chapters(chapters: &[MediaChapter], segments: &[TimeRange], crossfade: f64) -> Vec<MediaChapter> {
+    let mut rebased: Vec<MediaChapter> = vec![];
+
+    for chapter in chapters {
+        // Whether the previous piece of the chapter reaches the end of its segment
+        let mut continued = false;
+        let mut offset = 0.0;
+
+        for segment in segments {
+            let (start, end) = (chapter.start.max(segment.start), chapter.end.min(segment.end));
+
+            if end - start > MIN_CHAPTER_DURATION {
+                let (piece_start, piece_end) = (offset + start - segment.start, offset + end - segment.start);
+
+                // Pieces on both sides of a cut are joined back together
+                match rebased.last_mut() {
+                    Some(last) if continued && start <= segment.start => last.end = piece_end,
+                    _ => rebased.push(MediaChapter {
+                        start: piece_start,
+                        end: piece_end,
+                        title: chapter.title.clone(),
+                    }),
+                }
+                continued = end >= segment.end;
+            } else {
+                continued = false;
+            }
+
+            offset += segment.duration() - crossfade;
+        }
+    }
+
+    rebased.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    // Crossfades overlap the end of one segment with the start of the next one
+    for i in 1..rebased.len() {
+        let previous_end = rebased[i - 1].end;
+        rebased[i].start = rebased[i].start.max(previous_end);
+    }
+    rebased.retain(|chapter| chapter.end - chapter.start > MIN_CHAPTER_DURATION);
+
+    rebased
+}
+
+/// ffmetadata file contents with `chapters` in milliseconds
+pub fn chapters_ffmetadata(chapters: &[MediaChapter]) -> String {
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        metadata += "[CHAPTER]\nTIMEBASE=1/1000\n";
+        metadata += &format!("START={}\n", (chapter.start * 1000.0).round() as i64);
+        metadata += &format!("END={}\n", (chapter.end * 1000.0).round() as i64);
+        if let Some(title) = &chapter.title {
+            metadata += &format!("title={}\n", escape_ffmetadata_value(title));
+        }
+    }
+    metadata
+}
+
+fn escape_ffmetadata_value(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(|c| ['=', ';', '#', '\\', '\n'].contains(&c).then_some('\\').into_iter().chain([c]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(start: f64, end: f64, title: &str) -> MediaChapter {
+        MediaChapter {
+            start,
+            end,
+            title: Some(title.to_string()),
+        }
+    }
+
+    fn spans(chapters: &[MediaChapter]) -> Vec<(f64, f64, Option<&str>)> {
+        chapters.iter().map(|c| (c.start, c.end, c.title.as_deref())).collect()
+    }
+
+    #[test]
+    fn chapters_are_clipped_to_the_segments() {
+        let chapters = [chapter(0.0, 10.0, "A"), chapter(10.0, 20.0, "B"), chapter(20.0, 30.0, "C")];
+        let segments = [TimeRange { start: 5.0, end: 12.0 }, TimeRange { start: 25.0, end: 30.0 }];
+
+        assert_eq!(
+            spans(&rebase_chapters(&chapters, &segments, 0.0)),
+            [(0.0, 5.0, Some("A")), (5.0, 7.0, Some("B")), (7.0, 12.0, Some("C"))]
+        );
+    }
+
+    #[test]
+    fn chapters_spanning_a_cut_stay_one_chapter() {
+        let chapters = [chapter(0.0, 30.0, "A")];
+        let segments = [TimeRange { start: 0.0, end: 10.0 }, TimeRange { start: 20.0, end: 25.0 }];
+
+        assert_eq!(spans(&rebase_chapters(&chapters, &segments, 0.0)), [(0.0, 15.0, Some("A"))]);
+    }
+
+    #[test]
+    fn crossfades_shorten_the_following_chapter() {
+        let chapters = [chapter(0.0, 10.0, "A"), chapter(10.0, 20.0, "B")];
+        let segments = [TimeRange { start: 0.0, end: 10.0 }, TimeRange { start: 10.0, end: 20.0 }];
+
+        assert_eq!(
+            spans(&rebase_chapters(&chapters, &segments, 2.0)),
+            [(0.0, 10.0, Some("A")), (10.0, 18.0, Some("B"))]
+        );
+    }
+
+    #[test]
+    fn markers_start_chapters_until_the_next_marker() {
+        let marker = |time, title: &str| ChapterMarker {
+            time,
+            title: Some(title.to_string()),
+        };
+        let markers = [marker(5.0, "Intro"), marker(0.0, ""), marker(5.0, "Duplicate"), marker(40.0, "Outro")];
+
+        assert_eq!(
+            spans(&markers_to_chapters(&markers, 30.0)),
+            [(0.0, 5.0, None), (5.0, 30.0, Some("Intro"))]
+        );
+    }
+
+    #[test]
+    fn ffmetadata_has_millisecond_chapters_with_escaped_titles() {
+        let chapters = [chapter(0.0, 1.5, "A=B; #1")];
+
+        assert_eq!(
+            chapters_ffmetadata(&chapters),
+            ";FFMETADATA1\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=1500\ntitle=A\\=B\\; \\#1\n"
+        );
+    }
+}
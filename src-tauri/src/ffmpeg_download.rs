@@ -5,14 +5,22 @@ use log::{debug, info};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-pub fn download_with_progress(progress_callback: impl Fn(f64)) -> Result<()> {
+/// Downloads and unpacks ffmpeg, the download is aborted as soon as `is_cancelled` returns `true`
+pub fn download_with_progress(progress_callback: impl Fn(f64), is_cancelled: impl Fn() -> bool) -> Result<()> {
     progress_callback(0.0);
     let download_url = ffmpeg_download_url()?;
     let destination = sidecar_dir()?;
     info!("{:?}", destination);
-    let archive_path = download_ffmpeg_package_with_progress(download_url, &destination, |(total, downloaded)| {
-        progress_callback(downloaded as f64 / total as f64)
-    })?;
+    let archive_path = download_ffmpeg_package_with_progress(
+        download_url,
+        &destination,
+        |(total, downloaded)| progress_callback(downloaded as f64 / total as f64),
+        &is_cancelled,
+    )?;
+    if is_cancelled() {
+        remove_archive(&archive_path);
+        anyhow::bail!("FFmpeg download cancelled");
+    }
     progress_callback(0.0);
     unpack_ffmpeg(&archive_path, &destination)?;
     progress_callback(1.0);
@@ -24,7 +32,12 @@ pub fn download_with_progress(progress_callback: impl Fn(f64)) -> Result<()> {
     Ok(())
 }
 
-pub fn download_ffmpeg_package_with_progress(url: &str, download_dir: &Path, progress_callback: impl Fn((u64, u64))) -> Result<PathBuf> {
+pub fn download_ffmpeg_package_with_progress(
+    url: &str,
+    download_dir: &Path,
+    progress_callback: impl Fn((u64, u64)),
+    is_cancelled: impl Fn() -> bool,
+) -> Result<PathBuf> {
     use anyhow::Context;
     use std::{
         fs::File,
@@ -51,16 +64,21 @@ pub fn download_ffmpeg_package_with_progress(url: &str, download_dir: &Path, pro
     let mut file = File::create(&archive_path).context("Failed to create file for ffmpeg download")?;
 
     // Wrapper to track progress during io::copy
-    struct ProgressReader<R, F> {
+    struct ProgressReader<R, F, C> {
         inner: R,
         progress_callback: F,
+        is_cancelled: C,
         downloaded: u64,
         total: u64,
         counter: u64,
     }
 
-    impl<R: Read, F: Fn((u64, u64))> Read for ProgressReader<R, F> {
+    impl<R: Read, F: Fn((u64, u64)), C: Fn() -> bool> Read for ProgressReader<R, F, C> {
         fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if (self.is_cancelled)() {
+                return Err(std::io::Error::other("Download cancelled"));
+            }
+
             let n = self.inner.read(buf)?;
             self.downloaded += n as u64;
             self.counter += 1;
@@ -77,12 +95,24 @@ pub fn download_ffmpeg_package_with_progress(url: &str, download_dir: &Path, pro
     let mut progress_reader = ProgressReader {
         inner: response.body_mut().as_reader(),
         progress_callback,
+        is_cancelled,
         downloaded: 0,
         total: total_size,
         counter: 0,
     };
 
-    copy(&mut progress_reader, &mut file).context("Failed to write ffmpeg download to file")?;
+    if let Err(e) = copy(&mut progress_reader, &mut file) {
+        drop(file);
+        remove_archive(&archive_path);
+        return Err(e).context("Failed to write ffmpeg download to file");
+    }
 
     Ok(archive_path)
 }
+
+/// Removes partially downloaded archive
+fn remove_archive(archive_path: &Path) {
+    if let Err(e) = fs::remove_file(archive_path) {
+        debug!("Failed to remove ffmpeg archive {:?}: {e}", archive_path);
+    }
+}
@@ -0,0 +1,368 @@
+use crate::ffmpeg_export_command::{ExportOptions, RateControl};
+use log::warn;
+
+/// Encoder preset used when none is selected
+pub const DEFAULT_ENCODER_PRESET: &str = "medium";
+
+/// x264 style preset names from the fastest to the slowest
+const NAMED_PRESETS: [&str; 9] = [
+    "ultrafast",
+    "superfast",
+    "veryfast",
+    "faster",
+    "fast",
+    "medium",
+    "slow",
+    "slower",
+    "veryslow",
+];
+
+/// NVENC presets matching [`NAMED_PRESETS`]
+const NVENC_PRESETS: [&str; 9] = ["p1", "p1", "p2", "p3", "p3", "p4", "p5", "p6", "p7"];
+
+/// SVT-AV1 presets matching [`NAMED_PRESETS`]
+const SVT_AV1_PRESETS: [&str; 9] = ["12", "11", "10", "9", "8", "6", "4", "3", "2"];
+
+/// Group of encoders sharing rate control and preset options
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EncoderFamily {
+    /// libx264 and the default encoders of most containers
+    X26x,
+    /// libx265, which takes two-pass options through `-x265-params`
+    X265,
+    Nvenc,
+    SvtAv1,
+    /// libvpx and libaom, which need `-b:v 0` for constant quality
+    Vpx,
+    Other,
+}
+
+impl EncoderFamily {
+    pub fn from_codec(codec: Option<&str>) -> Self {
+        match codec {
+            None => EncoderFamily::X26x,
+            Some(codec) if codec.contains("nvenc") => EncoderFamily::Nvenc,
+            Some("libx264" | "h264") => EncoderFamily::X26x,
+            Some("libx265" | "hevc") => EncoderFamily::X265,
+            Some("libsvtav1") => EncoderFamily::SvtAv1,
+            Some("libvpx" | "libvpx-vp9" | "libaom-av1") => EncoderFamily::Vpx,
+            Some(_) => EncoderFamily::Other,
+        }
+    }
+
+    /// Arguments selecting `preset` (x264 style name or encoder specific value) and `tune`
+    pub fn preset_args(&self, preset: Option<&str>, tune: Option<&str>) -> Vec<String> {
+        let preset = preset.unwrap_or(DEFAULT_ENCODER_PRESET);
+        let preset_index = NAMED_PRESETS.iter().position(|p| *p == preset);
+
+        let preset = match self {
+            EncoderFamily::X26x | EncoderFamily::X265 => Some(preset.to_string()),
+            EncoderFamily::Nvenc => Some(preset_index.map(|i| NVENC_PRESETS[i]).unwrap_or(preset).to_string()),
+            EncoderFamily::SvtAv1 => Some(preset_index.map(|i| SVT_AV1_PRESETS[i]).unwrap_or(preset).to_string()),
+            // libvpx and libaom use -deadline and -cpu-used instead of presets
+            EncoderFamily::Vpx | EncoderFamily::Other => None,
+        };
+
+        let mut args = vec![];
+
+        if let Some(preset) = preset {
+            args.extend(["-preset".to_string(), preset]);
+        }
+
+        if let Some(tune) = tune {
+            args.extend(["-tune".to_string(), tune.to_string()]);
+        }
+
+        args
+    }
+
+    pub fn rate_control_args(&self, rate_control: &RateControl) -> Vec<String> {
+        match rate_control {
+            RateControl::ConstantQuality { quality } => match self {
+                EncoderFamily::Nvenc => vec![
+                    "-rc".to_string(),
+                    "vbr".to_string(),
+                    "-cq".to_string(),
+                    quality.to_string(),
+                    "-b:v".to_string(),
+                    "0".to_string(),
+                ],
+                EncoderFamily::Vpx => vec!["-crf".to_string(), quality.to_string(), "-b:v".to_string(), "0".to_string()],
+                EncoderFamily::X26x | EncoderFamily::X265 | EncoderFamily::SvtAv1 | EncoderFamily::Other => {
+                    vec!["-crf".to_string(), quality.to_string()]
+                }
+            },
+            RateControl::AverageBitrate { bitrate } => vec!["-b:v".to_string(), bitrate.clone()],
+            RateControl::ConstrainedVbr {
+                bitrate,
+                max_bitrate,
+                buffer_size,
+            } => {
+                let mut args = vec![];
+                if *self == EncoderFamily::Nvenc {
+                    args.extend(["-rc".to_string(), "vbr".to_string()]);
+                }
+                args.extend([
+                    "-b:v".to_string(),
+                    bitrate.clone(),
+                    "-maxrate".to_string(),
+                    max_bitrate.clone(),
+                    "-bufsize".to_string(),
+                    buffer_size.clone(),
+                ]);
+                args
+            }
+        }
+    }
+
+    /// Arguments running `pass` of a two-pass encode with statistics in files starting with `stats_prefix`,
+    /// `None` if the encoder has no two-pass mode
+    pub fn two_pass_args(&self, pass: u8, stats_prefix: &str) -> Option<Vec<String>> {
+        match self {
+            EncoderFamily::X26x | EncoderFamily::Vpx => Some(vec![
+                "-pass".to_string(),
+                pass.to_string(),
+                "-passlogfile".to_string(),
+                stats_prefix.to_string(),
+            ]),
+            // libx265 ignores -pass and -passlogfile
+            EncoderFamily::X265 => Some(vec![
+                "-x265-params".to_string(),
+                format!("pass={}:stats={}", pass, escape_x265_param(&format!("{}.log", stats_prefix))),
+            ]),
+            EncoderFamily::Nvenc | EncoderFamily::SvtAv1 | EncoderFamily::Other => None,
+        }
+    }
+}
+
+/// Escapes the `:` and `=` separators of `-x265-params` values, e.g. in Windows paths
+fn escape_x265_param(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(|c| [':', '=', '\\'].contains(&c).then_some('\\').into_iter().chain([c]))
+        .collect()
+}
+
+/// Audio codec of an ffmpeg audio encoder name, e.g. `opus` for `libopus`
+fn audio_codec_name(encoder: &str) -> &str {
+    match encoder {
+        "libopus" => "opus",
+        "libmp3lame" => "mp3",
+        "libvorbis" => "vorbis",
+        "libfdk_aac" => "aac",
+        encoder => encoder,
+    }
+}
+
+/// Audio codecs the container of `extension` can hold, `None` if it isn't restricted
+fn container_audio_codecs(extension: &str) -> Option<&'static [&'static str]> {
+    match extension {
+        // Opus and FLAC are rejected by older MP4 muxers, PCM is only allowed in MOV
+        "mp4" | "m4v" => Some(&["aac", "mp3", "ac3", "eac3", "alac"]),
+        "mov" => Some(&["aac", "mp3", "ac3", "eac3", "alac", "pcm_s16le", "pcm_s24le", "pcm_s32le", "pcm_f32le"]),
+        "webm" => Some(&["opus", "vorbis"]),
+        "avi" => Some(&["mp3", "ac3", "pcm_s16le"]),
+        _ => None,
+    }
+}
+
+fn default_audio_encoder(extension: &str) -> &'static str {
+    match extension {
+        "webm" => "libopus",
+        "avi" => "libmp3lame",
+        _ => "aac",
+    }
+}
+
+fn max_audio_channels(codec: &str) -> Option<u32> {
+    match codec {
+        "mp3" => Some(2),
+        "ac3" | "eac3" => Some(6),
+        "aac" | "opus" | "vorbis" => Some(8),
+        _ => None,
+    }
+}
+
+fn supported_sample_rates(codec: &str) -> Option<&'static [u32]> {
+    match codec {
+        "opus" => Some(&[48000, 24000, 16000, 12000, 8000]),
+        "mp3" => Some(&[48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000]),
+        "ac3" | "eac3" => Some(&[48000, 44100, 32000]),
+        _ => None,
+    }
+}
+
+/// Checks the audio options against the output container and codec limits.
+/// Incompatible values are replaced when `fix_incompatible_audio` is set, otherwise the error describes them.
+pub fn validate_audio_options(options: &mut ExportOptions) -> Result<(), String> {
+    let extension = options.output_extension();
+    let fix = options.fix_incompatible_audio;
+
+    let encoder = options
+        .audio_codec
+        .clone()
+        .unwrap_or_else(|| default_audio_encoder(&extension).to_string());
+
+    let mut codec = audio_codec_name(&encoder).to_string();
+    if let Some(codecs) = container_audio_codecs(&extension)
+        && !codecs.contains(&codec.as_str())
+    {
+        let fallback = default_audio_encoder(&extension);
+        if !fix {
+            return Err(format!(
+                "{} audio can't be stored in {} files, use {} instead",
+                encoder, extension, fallback
+            ));
+        }
+        warn!("{} audio can't be stored in {} files, using {} instead", encoder, extension, fallback);
+        options.audio_codec = Some(fallback.to_string());
+        codec = audio_codec_name(fallback).to_string();
+    }
+
+    if let Some(max_channels) = max_audio_channels(&codec)
+        && let Some(channels) = options.audio_channels.filter(|channels| *channels > max_channels)
+    {
+        if !fix {
+            return Err(format!("{} audio supports at most {} channels, got {}", codec, max_channels, channels));
+        }
+        warn!(
+            "{} audio supports at most {} channels, downmixing {} channels",
+            codec, max_channels, channels
+        );
+        options.audio_channels = Some(max_channels);
+    }
+
+    if let Some(sample_rates) = supported_sample_rates(&codec)
+        && let Some(sample_rate) = options.audio_sample_rate.filter(|rate| !sample_rates.contains(rate))
+    {
+        // Closest supported rate not below the requested one, or the highest one
+        let fallback = sample_rates
+            .iter()
+            .copied()
+            .filter(|rate| *rate >= sample_rate)
+            .min()
+            .unwrap_or(sample_rates[0]);
+        if !fix {
+            return Err(format!(
+                "{} audio doesn't support {} Hz sample rate, use {} Hz instead",
+                codec, sample_rate, fallback
+            ));
+        }
+        warn!(
+            "{} audio doesn't support {} Hz sample rate, using {} Hz instead",
+            codec, sample_rate, fallback
+        );
+        options.audio_sample_rate = Some(fallback);
+    }
+
+    Ok(())
+}
+
+/// Audio codec, bitrate, sample rate and channels arguments of the export output,
+/// `audio_bitrate_override` takes precedence over the selected bitrate
+pub fn audio_encoder_args(options: &ExportOptions, audio_bitrate_override: Option<&str>) -> Vec<String> {
+    let mut args = vec![];
+
+    if let Some(codec) = &options.audio_codec {
+        args.extend(["-c:a".to_string(), codec.clone()]);
+    }
+
+    let lossless = options
+        .audio_codec
+        .as_deref()
+        .is_some_and(|codec| codec.starts_with("pcm_") || matches!(codec, "flac" | "alac"));
+
+    if let Some(bitrate) = audio_bitrate_override.or(options.audio_bitrate.as_deref())
+        && !lossless
+    {
+        args.extend(["-b:a".to_string(), bitrate.to_string()]);
+    }
+
+    if let Some(sample_rate) = options.audio_sample_rate {
+        args.extend(["-ar".to_string(), sample_rate.to_string()]);
+    }
+
+    if let Some(channels) = options.audio_channels {
+        args.extend(["-ac".to_string(), channels.to_string()]);
+    }
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export_options(output_path: &str) -> ExportOptions {
+        serde_json::from_value(serde_json::json!({
+            "startTime": 0.0,
+            "endTime": 10.0,
+            "inputPath": "input.mp4",
+            "outputPath": output_path,
+            "activeAudioStreams": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn two_pass_args_per_encoder() {
+        assert_eq!(
+            EncoderFamily::X26x.two_pass_args(2, "/tmp/export_1"),
+            Some(vec![
+                "-pass".to_string(),
+                "2".to_string(),
+                "-passlogfile".to_string(),
+                "/tmp/export_1".to_string()
+            ])
+        );
+        assert_eq!(
+            EncoderFamily::X265.two_pass_args(1, r"C:\tmp\export_1"),
+            Some(vec!["-x265-params".to_string(), r"pass=1:stats=C\:\\tmp\\export_1.log".to_string()])
+        );
+        assert_eq!(EncoderFamily::Nvenc.two_pass_args(1, "/tmp/export_1"), None);
+    }
+
+    #[test]
+    fn audio_codec_must_fit_the_container() {
+        let mut options = export_options("output.webm");
+        options.audio_codec = Some("aac".to_string());
+        assert!(validate_audio_options(&mut options).is_err());
+
+        options.fix_incompatible_audio = true;
+        assert!(validate_audio_options(&mut options).is_ok());
+        assert_eq!(options.audio_codec.as_deref(), Some("libopus"));
+    }
+
+    #[test]
+    fn audio_channels_and_sample_rate_are_fixed_for_the_codec() {
+        let mut options = export_options("output.mp4");
+        options.audio_codec = Some("libmp3lame".to_string());
+        options.audio_channels = Some(6);
+        options.audio_sample_rate = Some(96000);
+        options.fix_incompatible_audio = true;
+
+        assert!(validate_audio_options(&mut options).is_ok());
+        assert_eq!(options.audio_channels, Some(2));
+        assert_eq!(options.audio_sample_rate, Some(48000));
+
+        let mut options = export_options("output.webm");
+        options.audio_sample_rate = Some(44100);
+        options.fix_incompatible_audio = true;
+
+        assert!(validate_audio_options(&mut options).is_ok());
+        assert_eq!(options.audio_sample_rate, Some(48000));
+    }
+
+    #[test]
+    fn unrestricted_containers_keep_audio_options() {
+        let mut options = export_options("output.mkv");
+        options.audio_codec = Some("flac".to_string());
+        options.audio_channels = Some(8);
+        options.audio_sample_rate = Some(96000);
+
+        assert!(validate_audio_options(&mut options).is_ok());
+        assert_eq!(options.audio_codec.as_deref(), Some("flac"));
+        assert_eq!(options.audio_channels, Some(8));
+        assert_eq!(options.audio_sample_rate, Some(96000));
+    }
+}
@@ -0,0 +1,1468 @@
+use crate::APP_IDENTIFIER;
+use crate::ffmpeg_chapters::{chapters_ffmetadata, export_chapters};
+use crate::ffmpeg_encoder::{EncoderFamily, audio_encoder_args};
+use crate::ffmpeg_export_command::{BurnInSubtitles, ConcatOptions, ExportAudioStreamOptions, ExportOptions, GpuAcceleration, TimeRange};
+use crate::ffmpeg_path::ffmpeg_path;
+use crate::ffmpeg_task_error::FfmpegTaskError;
+use crate::ffprobe::{
+    MediaProbe, MediaStream, ProbeError, VideoCodecParameters, get_video_codec_parameters, indexed_video_keyframes, probe_media_blocking,
+    scan_video_keyframes_blocking,
+};
+use ffmpeg_sidecar::command::FfmpegCommand;
+use log::{info, warn};
+use std::borrow::Cow;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How far around each cut point keyframes are searched for
+const KEYFRAME_SEARCH_WINDOW: f64 = 30.0;
+
+/// Added to keyframe seek points so a rounded timestamp doesn't land just before the keyframe and a GOP earlier
+const KEYFRAME_SEEK_EPSILON: f64 = 0.001;
+
+/// Segments shorter than this are not worth a separate encode
+const MIN_SEGMENT_DURATION: f64 = 0.001;
+
+/// Audio bitrate of target size exports without a selected audio bitrate
+const DEFAULT_TARGET_SIZE_AUDIO_BITRATE: &str = "128k";
+
+/// Share of the target size reserved for the container
+const TARGET_SIZE_CONTAINER_OVERHEAD: f64 = 0.02;
+
+const TARGET_SIZE_MIN_VIDEO_BITRATE_KBPS: f64 = 50.0;
+
+const DEFAULT_CONCAT_FRAME_RATE: f64 = 30.0;
+const DEFAULT_CONCAT_SAMPLE_RATE: u32 = 48000;
+
+/// Sequence of ffmpeg commands producing the export output
+pub struct ExportPlan {
+    pub steps: Vec<ExportStep>,
+    /// Intermediate files to remove once the plan finished
+    pub temp_files: Vec<PathBuf>,
+    /// Prefixes of intermediate files named by the encoder, e.g. two-pass statistics,
+    /// every file starting with one is removed once the plan finished
+    pub temp_file_prefixes: Vec<PathBuf>,
+    /// Paths and contents of intermediate text files written right before the first step,
+    /// so a plan that fails to build or never runs leaves nothing behind. They are listed in `temp_files` too.
+    pub temp_file_contents: Vec<(PathBuf, String)>,
+    /// Cut ranges actually used by the plan
+    pub segments: Vec<TimeRange>,
+}
+
+pub struct ExportStep {
+    pub command: FfmpegCommand,
+    /// Duration of the step output, used to compute the task progress
+    pub duration: f64,
+}
+
+impl ExportPlan {
+    fn single(command: FfmpegCommand, segments: Vec<TimeRange>) -> Self {
+        Self {
+            steps: vec![ExportStep {
+                command,
+                duration: TimeRange::total_duration(&segments),
+            }],
+            temp_files: vec![],
+            temp_file_prefixes: vec![],
+            temp_file_contents: vec![],
+            segments,
+        }
+    }
+
+    pub fn total_duration(&self) -> f64 {
+        self.steps.iter().map(|s| s.duration).sum()
+    }
+
+    /// Runs the steps preparing `subtitle_track` before the plan steps
+    fn with_subtitle_track(mut self, subtitle_track: Option<SubtitleTrack>) -> Self {
+        if let Some(subtitle_track) = subtitle_track {
+            self.steps.splice(0..0, subtitle_track.steps);
+            self.temp_files.extend(subtitle_track.temp_files);
+            self.temp_file_contents.extend(subtitle_track.temp_file_contents);
+        }
+        self
+    }
+
+    /// Writes the output chapters metadata file before the plan steps and removes it once the plan finished
+    fn with_chapters_metadata(mut self, chapters_metadata: Option<ChaptersMetadata>) -> Self {
+        if let Some(chapters_metadata) = chapters_metadata {
+            self.temp_files.push(chapters_metadata.path.clone());
+            self.temp_file_contents.push((chapters_metadata.path, chapters_metadata.contents));
+        }
+        self
+    }
+}
+
+/// Output chapters as an ffmetadata file
+struct ChaptersMetadata {
+    path: PathBuf,
+    contents: String,
+}
+
+/// Subtitle stream kept in the output and the codec it's stored with in the output container
+struct SubtitleOutput {
+    index: usize,
+    codec: &'static str,
+}
+
+/// Subtitles cut to the exported segments and joined into one file, so they stay in sync with the joined video
+struct SubtitleTrack {
+    steps: Vec<ExportStep>,
+    temp_files: Vec<PathBuf>,
+    temp_file_contents: Vec<(PathBuf, String)>,
+    path: PathBuf,
+}
+
+pub fn reencode_export_plan(options: &ExportOptions) -> Result<ExportPlan, FfmpegTaskError> {
+    let subtitle_track = reencode_subtitle_track(options)?;
+    let chapters_metadata = reencode_chapters_metadata(options)?;
+    let mut reencode = reencode_command(options, None, None, subtitle_track.as_ref(), chapters_metadata.as_ref())?;
+    reencode.command.output(&options.output_path);
+
+    let export_plan = ExportPlan {
+        steps: vec![ExportStep {
+            command: reencode.command,
+            duration: reencode.duration,
+        }],
+        temp_files: vec![],
+        temp_file_prefixes: vec![],
+        temp_file_contents: vec![],
+        segments: reencode.segments,
+    };
+
+    Ok(export_plan.with_subtitle_track(subtitle_track).with_chapters_metadata(chapters_metadata))
+}
+
+/// Two-pass encode of the whole export with `video_bitrate_kbps` average bitrate,
+/// NVENC is limited by maxrate in its own multipass mode instead
+pub fn two_pass_export_plan(options: &ExportOptions, video_bitrate_kbps: f64) -> Result<ExportPlan, FfmpegTaskError> {
+    let video_bitrate = format!("{}k", video_bitrate_kbps.floor());
+    let audio_bitrate = target_size_audio_bitrate(options);
+    let subtitle_track = reencode_subtitle_track(options)?;
+    let chapters_metadata = reencode_chapters_metadata(options)?;
+
+    let encoder = EncoderFamily::from_codec(options.video_codec.as_deref());
+
+    if encoder == EncoderFamily::Nvenc {
+        let mut reencode = reencode_command(
+            options,
+            Some(&video_bitrate),
+            Some(audio_bitrate),
+            subtitle_track.as_ref(),
+            chapters_metadata.as_ref(),
+        )?;
+        let buffer_size = format!("{}k", (video_bitrate_kbps * 2.0).floor());
+        reencode
+            .command
+            .args([
+                "-maxrate",
+                video_bitrate.as_str(),
+                "-bufsize",
+                buffer_size.as_str(),
+                "-multipass",
+                "fullres",
+            ])
+            .output(&options.output_path);
+
+        let export_plan = ExportPlan {
+            steps: vec![ExportStep {
+                command: reencode.command,
+                duration: reencode.duration,
+            }],
+            temp_files: vec![],
+            temp_file_prefixes: vec![],
+            temp_file_contents: vec![],
+            segments: reencode.segments,
+        };
+
+        return Ok(export_plan.with_subtitle_track(subtitle_track).with_chapters_metadata(chapters_metadata));
+    }
+
+    let pass_log_file = temp_dir()?.join(format!("{}_pass", temp_file_prefix()));
+    let pass_log_file_str = pass_log_file.to_string_lossy().to_string();
+
+    let (Some(first_pass_args), Some(second_pass_args)) =
+        (encoder.two_pass_args(1, &pass_log_file_str), encoder.two_pass_args(2, &pass_log_file_str))
+    else {
+        return Err(FfmpegTaskError::InvalidOptions(format!(
+            "{} doesn't support two-pass encoding needed for target size exports",
+            options.video_codec.as_deref().unwrap_or("The selected encoder")
+        )));
+    };
+
+    let mut first_pass = reencode_command(options, Some(&video_bitrate), Some(audio_bitrate), None, None)?;
+    first_pass.command.args(first_pass_args).format("null").output("-");
+
+    let mut second_pass = reencode_command(
+        options,
+        Some(&video_bitrate),
+        Some(audio_bitrate),
+        subtitle_track.as_ref(),
+        chapters_metadata.as_ref(),
+    )?;
+    second_pass.command.args(second_pass_args).output(&options.output_path);
+
+    let export_plan = ExportPlan {
+        steps: vec![
+            ExportStep {
+                command: first_pass.command,
+                duration: first_pass.duration,
+            },
+            ExportStep {
+                command: second_pass.command,
+                duration: second_pass.duration,
+            },
+        ],
+        temp_files: vec![],
+        temp_file_prefixes: vec![pass_log_file],
+        temp_file_contents: vec![],
+        segments: second_pass.segments,
+    };
+
+    Ok(export_plan.with_subtitle_track(subtitle_track).with_chapters_metadata(chapters_metadata))
+}
+
+/// Average video bitrate fitting the export into `target_size_bytes`
+/// after subtracting audio and container overhead
+pub fn target_size_video_bitrate_kbps(options: &ExportOptions, target_size_bytes: u64) -> Result<f64, FfmpegTaskError> {
+    let segments = options.segments();
+    let duration = output_duration(&segments, reencode_crossfade_duration(options, &segments)?);
+    if duration <= 0.0 {
+        return Err(FfmpegTaskError::InvalidOptions("Nothing to export".to_string()));
+    }
+
+    let total_kbps = target_size_bytes as f64 * 8.0 / 1000.0 / duration * (1.0 - TARGET_SIZE_CONTAINER_OVERHEAD);
+    let audio_kbps = bitrate_kbps(target_size_audio_bitrate(options)).unwrap_or_default() * audio_output_labels(options).len() as f64;
+
+    let video_kbps = total_kbps - audio_kbps;
+    if video_kbps < TARGET_SIZE_MIN_VIDEO_BITRATE_KBPS {
+        return Err(FfmpegTaskError::InvalidOptions(format!(
+            "Target size of {} bytes is too small for {} seconds of video ({} kbps left for video)",
+            target_size_bytes, duration, video_kbps
+        )));
+    }
+
+    Ok(video_kbps)
+}
+
+fn target_size_audio_bitrate(options: &ExportOptions) -> &str {
+    options.audio_bitrate.as_deref().unwrap_or(DEFAULT_TARGET_SIZE_AUDIO_BITRATE)
+}
+
+/// Parses ffmpeg bitrate like `128k`, `2.5M` or `96000` into kbps
+fn bitrate_kbps(bitrate: &str) -> Option<f64> {
+    let bitrate = bitrate.trim();
+    if let Some(kbps) = bitrate.strip_suffix(['k', 'K']) {
+        kbps.parse().ok()
+    } else if let Some(mbps) = bitrate.strip_suffix('M') {
+        mbps.parse::<f64>().ok().map(|mbps| mbps * 1000.0)
+    } else {
+        bitrate.parse::<f64>().ok().map(|bps| bps / 1000.0)
+    }
+}
+
+struct ReencodeCommand {
+    /// Command with everything but the output set
+    command: FfmpegCommand,
+    segments: Vec<TimeRange>,
+    /// Output duration
+    duration: f64,
+}
+
+/// Re-encode of the export filter graph, bitrate overrides take precedence over the selected rate control and bitrates.
+/// Subtitles of `subtitle_track` and chapters of `chapters_metadata` are added as the following inputs.
+fn reencode_command(
+    options: &ExportOptions,
+    video_bitrate_override: Option<&str>,
+    audio_bitrate_override: Option<&str>,
+    subtitle_track: Option<&SubtitleTrack>,
+    chapters_metadata: Option<&ChaptersMetadata>,
+) -> Result<ReencodeCommand, FfmpegTaskError> {
+    let segments = options.segments();
+    let (seek_start, seek_end) = segments_bounds(&segments)?;
+
+    let probe = probe_media_blocking(&options.input_path)?;
+
+    let burn_in_filter = match &options.burn_in_subtitles {
+        Some(subtitles) => Some(burn_in_subtitles_filter(options, subtitles, &probe, seek_start)?),
+        None => None,
+    };
+
+    let gpu_acceleration = gpu_decoding(options, &probe);
+
+    let scale = if let Some(resolution) = &options.resolution {
+        let scale_filter = gpu_acceleration.map(|(_, _, scale_filter)| scale_filter).unwrap_or("scale");
+        Cow::Owned(format!(",{}={}", scale_filter, resolution))
+    } else {
+        Cow::Borrowed("")
+    };
+
+    // Input is seeked to the first segment, so trims are relative to its start
+    let relative_segments = relative_to(&segments, seek_start);
+
+    let crossfade = crossfade_duration(options, &segments, gpu_acceleration.is_some());
+
+    let video_filter = match &burn_in_filter {
+        Some(burn_in_filter) => format!(
+            "{}{}",
+            burn_in_filter,
+            video_segments_filter("[vsub]", &relative_segments, crossfade, &scale)
+        ),
+        None => video_segments_filter("[0:v]", &relative_segments, crossfade, &scale),
+    };
+
+    let audio_filter = audio_segments_filter(options, 0, &relative_segments, crossfade);
+
+    let mut ffmpeg_command = FfmpegCommand::new_with_path(ffmpeg_path());
+
+    if let Some((args, codec, _)) = gpu_acceleration {
+        ffmpeg_command.args(args.split_whitespace());
+        ffmpeg_command.codec_video(codec);
+    }
+
+    ffmpeg_command.seek(seek_start.to_string().as_str());
+    ffmpeg_command.to(seek_end.to_string().as_str());
+
+    ffmpeg_command.input(&options.input_path);
+    if let Some(subtitle_track) = subtitle_track {
+        ffmpeg_command.input(subtitle_track.path.to_string_lossy());
+    }
+    input_chapters(&mut ffmpeg_command, chapters_metadata, 1 + usize::from(subtitle_track.is_some()));
+
+    ffmpeg_command
+        .overwrite()
+        .filter_complex(format!("{};{}", video_filter, audio_filter))
+        .map("[v]");
+    map_audio_outputs(&mut ffmpeg_command, options, 0);
+
+    if subtitle_track.is_some() {
+        ffmpeg_command.map("1:s").args(["-c:s", "copy"]);
+    }
+
+    if let Some(codec) = &options.video_codec {
+        ffmpeg_command.codec_video(codec);
+    }
+
+    let encoder = EncoderFamily::from_codec(options.video_codec.as_deref());
+
+    match (video_bitrate_override, &options.rate_control, &options.bitrate) {
+        (Some(bitrate), _, _) => {
+            ffmpeg_command.args(["-b:v", bitrate]);
+        }
+        (None, Some(rate_control), _) => {
+            ffmpeg_command.args(encoder.rate_control_args(rate_control));
+        }
+        (None, None, Some(bitrate)) => {
+            ffmpeg_command.args(["-b:v", bitrate.as_str()]);
+        }
+        (None, None, None) => {}
+    }
+
+    if let Some(frame_rate) = options.frame_rate {
+        ffmpeg_command.arg("-r");
+        ffmpeg_command.arg(frame_rate.to_string().as_str());
+    }
+
+    ffmpeg_command.args(encoder.preset_args(options.encoder_preset.as_deref(), options.encoder_tune.as_deref()));
+
+    ffmpeg_command.args(audio_encoder_args(options, audio_bitrate_override));
+
+    Ok(ReencodeCommand {
+        command: ffmpeg_command,
+        duration: output_duration(&segments, crossfade),
+        segments,
+    })
+}
+
+pub fn stream_copy_export_plan(options: &ExportOptions) -> Result<ExportPlan, FfmpegTaskError> {
+    let segments = options.segments();
+    segments_bounds(&segments)?;
+
+    let keyframes = keyframes_around(options, &segments)?;
+
+    let snapped_segments: Vec<TimeRange> = segments
+        .iter()
+        .map(|segment| {
+            let start = snap_to_nearest_keyframe(&keyframes, segment.start).unwrap_or_else(|| {
+                warn!("No keyframes found, the copy of {:?} starts at the keyframe before it", segment);
+                segment.start
+            });
+            let end = snap_to_nearest_keyframe(&keyframes, segment.end)
+                .filter(|end| *end > start)
+                .unwrap_or(segment.end);
+            TimeRange { start, end }
+        })
+        .collect();
+
+    info!("Stream copy cut points snapped from {:?} to {:?}", segments, snapped_segments);
+
+    if options.active_audio_streams.iter().any(|stream| stream.gain != 1.0) {
+        warn!("Audio gain is ignored in stream copy mode");
+    }
+
+    if options.crossfade_duration.is_some() {
+        warn!("Crossfades are ignored in stream copy mode");
+    }
+
+    if !audio_encoder_args(options, None).is_empty() {
+        warn!("Audio encoding options are ignored in stream copy mode");
+    }
+
+    if options.burn_in_subtitles.is_some() {
+        warn!("Burned in subtitles are ignored in stream copy mode");
+    }
+
+    let subtitle_outputs = subtitle_outputs(options);
+
+    let temp_prefix = temp_file_prefix();
+    let chapters_metadata = chapters_metadata(options, &snapped_segments, 0.0, &temp_prefix)?;
+
+    if let [segment] = snapped_segments[..] {
+        let command = stream_copy_command(
+            options,
+            segment,
+            true,
+            &subtitle_outputs,
+            chapters_metadata.as_ref(),
+            &options.output_path,
+        );
+        return Ok(ExportPlan::single(command, snapped_segments).with_chapters_metadata(chapters_metadata));
+    }
+
+    let output_extension = options.output_extension();
+
+    let mut steps = vec![];
+    let mut temp_files = vec![];
+
+    for (i, segment) in snapped_segments.iter().enumerate() {
+        let path = temp_dir()?.join(format!("{}_{}.{}", temp_prefix, i, output_extension));
+        steps.push(ExportStep {
+            command: stream_copy_command(options, *segment, true, &subtitle_outputs, None, &path.to_string_lossy()),
+            duration: segment.duration(),
+        });
+        temp_files.push(path);
+    }
+
+    let (concat_list_path, concat_list) = concat_list_file(&temp_prefix, &temp_files, None)?;
+
+    let mut ffmpeg_command = FfmpegCommand::new_with_path(ffmpeg_path());
+    ffmpeg_command
+        .format("concat")
+        .args(["-safe", "0"])
+        .input(concat_list_path.to_string_lossy());
+    input_chapters(&mut ffmpeg_command, chapters_metadata.as_ref(), 1);
+    ffmpeg_command
+        .overwrite()
+        .map("0")
+        .codec_video("copy")
+        .codec_audio("copy")
+        .args(audio_disposition_args(options));
+    if !subtitle_outputs.is_empty() {
+        ffmpeg_command.args(["-c:s", "copy"]);
+    }
+    ffmpeg_command.output(&options.output_path);
+    steps.push(ExportStep {
+        command: ffmpeg_command,
+        duration: TimeRange::total_duration(&snapped_segments),
+    });
+
+    temp_files.push(concat_list_path.clone());
+
+    Ok(ExportPlan {
+        steps,
+        temp_files,
+        temp_file_prefixes: vec![],
+        temp_file_contents: vec![(concat_list_path, concat_list)],
+        segments: snapped_segments,
+    }
+    .with_chapters_metadata(chapters_metadata))
+}
+
+enum SmartRenderPiece {
+    Encode(TimeRange),
+    Copy(TimeRange),
+}
+
+/// Frame accurate cut which re-encodes only the partial GOPs at the cut points
+/// and stream copies everything between the first and the last keyframe of each segment.
+/// Falls back to [`reencode_export_plan`] when the source can't be smart rendered.
+pub fn smart_render_export_plan(options: &ExportOptions) -> Result<ExportPlan, FfmpegTaskError> {
+    let segments = options.segments();
+    let (seek_start, seek_end) = segments_bounds(&segments)?;
+
+    if options.burn_in_subtitles.is_some() {
+        info!("Subtitles are burned in, falling back to re-encode");
+        return reencode_export_plan(options);
+    }
+
+    let Some(codec_parameters) = get_video_codec_parameters(&options.input_path) else {
+        warn!("Failed to probe codec parameters, falling back to re-encode");
+        return reencode_export_plan(options);
+    };
+
+    let output_extension = options.output_extension();
+
+    let Some(encoder_args) = smart_render_encoder_args(&codec_parameters, &output_extension) else {
+        warn!(
+            "Smart render is not supported for {} codec, falling back to re-encode",
+            codec_parameters.codec_name
+        );
+        return reencode_export_plan(options);
+    };
+
+    if options.resolution.is_some() || options.frame_rate.is_some() || options.video_codec.is_some() {
+        warn!("Resolution, frame rate and video codec options are ignored in smart render mode");
+    }
+
+    if options.crossfade_duration.is_some() {
+        warn!("Crossfades are ignored in smart render mode");
+    }
+
+    let keyframes = keyframes_around(options, &segments)?;
+
+    let mut pieces = vec![];
+    for segment in &segments {
+        let copy_start = keyframes.iter().copied().find(|k| *k >= segment.start);
+        let copy_end = keyframes.iter().copied().rev().find(|k| *k <= segment.end);
+
+        match (copy_start, copy_end) {
+            (Some(copy_start), Some(copy_end)) if copy_start < copy_end => {
+                info!("Smart render of {:?}: copy {}-{}, re-encode the rest", segment, copy_start, copy_end);
+
+                if copy_start - segment.start > MIN_SEGMENT_DURATION {
+                    pieces.push(SmartRenderPiece::Encode(TimeRange {
+                        start: segment.start,
+                        end: copy_start,
+                    }));
+                }
+                pieces.push(SmartRenderPiece::Copy(TimeRange {
+                    start: copy_start,
+                    end: copy_end,
+                }));
+                if segment.end - copy_end > MIN_SEGMENT_DURATION {
+                    pieces.push(SmartRenderPiece::Encode(TimeRange {
+                        start: copy_end,
+                        end: segment.end,
+                    }));
+                }
+            }
+            _ => {
+                info!("No whole GOP inside of {:?}, re-encoding it", segment);
+                pieces.push(SmartRenderPiece::Encode(*segment));
+            }
+        }
+    }
+
+    let temp_prefix = temp_file_prefix();
+
+    let mut steps = vec![];
+    let mut temp_files = vec![];
+
+    for (i, piece) in pieces.into_iter().enumerate() {
+        let path = temp_dir()?.join(format!("{}_{}.{}", temp_prefix, i, output_extension));
+        let (command, range) = match piece {
+            SmartRenderPiece::Encode(range) => (smart_render_encode_command(options, range, &encoder_args, &path), range),
+            SmartRenderPiece::Copy(range) => (stream_copy_command(options, range, false, &[], None, &path.to_string_lossy()), range),
+        };
+        steps.push(ExportStep {
+            command,
+            duration: range.duration(),
+        });
+        temp_files.push(path);
+    }
+
+    let (concat_list_path, concat_list) = concat_list_file(&temp_prefix, &temp_files, None)?;
+
+    let subtitle_track = subtitle_track(options, &segments, 0.0, &temp_prefix)?;
+    let chapters_metadata = chapters_metadata(options, &segments, 0.0, &temp_prefix)?;
+
+    // Audio is encoded once for all segments, so it stays continuous across the pieces joins
+    let mut ffmpeg_command = FfmpegCommand::new_with_path(ffmpeg_path());
+    ffmpeg_command
+        .format("concat")
+        .args(["-safe", "0"])
+        .input(concat_list_path.to_string_lossy());
+    ffmpeg_command.seek(seek_start.to_string().as_str());
+    ffmpeg_command.to(seek_end.to_string().as_str());
+    ffmpeg_command.input(&options.input_path);
+    if let Some(subtitle_track) = &subtitle_track {
+        ffmpeg_command.input(subtitle_track.path.to_string_lossy());
+    }
+    input_chapters(&mut ffmpeg_command, chapters_metadata.as_ref(), 2 + usize::from(subtitle_track.is_some()));
+    ffmpeg_command
+        .overwrite()
+        .filter_complex(audio_segments_filter(options, 1, &relative_to(&segments, seek_start), 0.0))
+        .map("0:v");
+    map_audio_outputs(&mut ffmpeg_command, options, 1);
+    if subtitle_track.is_some() {
+        ffmpeg_command.map("2:s").args(["-c:s", "copy"]);
+    }
+    ffmpeg_command
+        .codec_video("copy")
+        .args(audio_encoder_args(options, None))
+        .output(&options.output_path);
+    steps.push(ExportStep {
+        command: ffmpeg_command,
+        duration: TimeRange::total_duration(&segments),
+    });
+
+    temp_files.push(concat_list_path.clone());
+
+    Ok(ExportPlan {
+        steps,
+        temp_files,
+        temp_file_prefixes: vec![],
+        temp_file_contents: vec![(concat_list_path, concat_list)],
+        segments,
+    }
+    .with_subtitle_track(subtitle_track)
+    .with_chapters_metadata(chapters_metadata))
+}
+
+/// Joins clips of one or several files, normalizing them to a common resolution, frame rate and sample rate
+pub fn concat_export_plan(options: &ConcatOptions) -> Result<ExportPlan, FfmpegTaskError> {
+    let first_clip = options
+        .clips
+        .first()
+        .ok_or_else(|| FfmpegTaskError::InvalidOptions("No clips to join".to_string()))?;
+    let first_clip_parameters = get_video_codec_parameters(&first_clip.input_path);
+
+    let resolution = match &options.resolution {
+        Some(resolution) => resolution.clone(),
+        None => match first_clip_parameters.as_ref().and_then(|p| Some((p.width?, p.height?))) {
+            Some((width, height)) => format!("{}:{}", width, height),
+            None => return Err(FfmpegTaskError::Prepare(format!("Resolution of {} is unknown", first_clip.input_path))),
+        },
+    };
+    let (width, height) = resolution
+        .split_once(':')
+        .or_else(|| resolution.split_once('x'))
+        .ok_or_else(|| FfmpegTaskError::InvalidOptions(format!("Invalid resolution {resolution}")))?;
+
+    let frame_rate = options
+        .frame_rate
+        .or_else(|| first_clip_parameters.as_ref()?.frame_rate)
+        .unwrap_or(DEFAULT_CONCAT_FRAME_RATE);
+    let sample_rate = options.sample_rate.unwrap_or(DEFAULT_CONCAT_SAMPLE_RATE);
+
+    let mut ffmpeg_command = FfmpegCommand::new_with_path(ffmpeg_path());
+
+    let mut filters = vec![];
+    for (i, clip) in options.clips.iter().enumerate() {
+        ffmpeg_command.seek(clip.start_time.to_string().as_str());
+        ffmpeg_command.to(clip.end_time.to_string().as_str());
+        ffmpeg_command.input(&clip.input_path);
+
+        filters.push(format!(
+            "[{i}:v]scale={width}:{height}:force_original_aspect_ratio=decrease,pad={width}:{height}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={frame_rate},format=yuv420p,setpts=PTS-STARTPTS[v{i}]"
+        ));
+
+        if clip.active_audio_streams.is_empty() {
+            // Generate silence
+            filters.push(format!("aevalsrc=0:d={}:s={}:c=stereo[a{}]", clip.duration(), sample_rate, i));
+        } else {
+            filters.push(format!(
+                "{};[am{i}]aresample={sample_rate},aformat=channel_layouts=stereo,asetpts=PTS-STARTPTS[a{i}]",
+                audio_mix_filter(&clip.active_audio_streams, i, &format!("am{}", i))
+            ));
+        }
+    }
+
+    filters.push(format!(
+        "{}concat=n={}:v=1:a=1[v][a]",
+        (0..options.clips.len()).map(|i| format!("[v{i}][a{i}]")).collect::<String>(),
+        options.clips.len()
+    ));
+
+    ffmpeg_command.overwrite().filter_complex(filters.join(";")).map("[v]").map("[a]");
+
+    if let Some(codec) = &options.video_codec {
+        ffmpeg_command.codec_video(codec);
+    }
+
+    if let Some(bitrate) = &options.bitrate {
+        ffmpeg_command.args(["-b:v", bitrate.as_str()]);
+    }
+
+    ffmpeg_command
+        .args(EncoderFamily::from_codec(options.video_codec.as_deref()).preset_args(None, None))
+        .output(&options.output_path);
+
+    Ok(ExportPlan {
+        steps: vec![ExportStep {
+            command: ffmpeg_command,
+            duration: options.clips.iter().map(|c| c.duration()).sum(),
+        }],
+        temp_files: vec![],
+        temp_file_prefixes: vec![],
+        temp_file_contents: vec![],
+        segments: vec![],
+    })
+}
+
+fn stream_copy_command(
+    options: &ExportOptions,
+    range: TimeRange,
+    with_audio: bool,
+    subtitle_outputs: &[SubtitleOutput],
+    chapters_metadata: Option<&ChaptersMetadata>,
+    output: &str,
+) -> FfmpegCommand {
+    let mut ffmpeg_command = FfmpegCommand::new_with_path(ffmpeg_path());
+
+    ffmpeg_command.seek((range.start + KEYFRAME_SEEK_EPSILON).to_string().as_str());
+    ffmpeg_command.to(range.end.to_string().as_str());
+
+    ffmpeg_command.input(&options.input_path);
+    input_chapters(&mut ffmpeg_command, chapters_metadata, 1);
+    ffmpeg_command.overwrite().map("0:v:0");
+
+    if with_audio {
+        for stream in &options.active_audio_streams {
+            ffmpeg_command.map(format!("0:{}", stream.index));
+        }
+        ffmpeg_command.codec_audio("copy").args(audio_disposition_args(options));
+    } else {
+        ffmpeg_command.no_audio();
+    }
+
+    map_subtitle_outputs(&mut ffmpeg_command, subtitle_outputs);
+
+    ffmpeg_command
+        .codec_video("copy")
+        .args(["-avoid_negative_ts", "make_zero"])
+        .output(output);
+
+    ffmpeg_command
+}
+
+/// Encoder arguments reproducing the source stream codec parameters,
+/// `None` if the codec has no known matching encoder
+fn smart_render_encoder_args(parameters: &VideoCodecParameters, output_extension: &str) -> Option<Vec<String>> {
+    let level = parameters.level.filter(|level| *level > 0);
+
+    let mut args = match parameters.codec_name.as_str() {
+        "h264" => {
+            let mut args = vec!["-c:v".to_string(), "libx264".to_string()];
+            if let Some(profile) = parameters.profile.as_deref().and_then(x264_profile) {
+                args.extend(["-profile:v".to_string(), profile.to_string()]);
+            }
+            if let Some(level) = level {
+                args.extend(["-level:v".to_string(), format!("{:.1}", level as f64 / 10.0)]);
+            }
+            args
+        }
+        "hevc" => {
+            let mut args = vec!["-c:v".to_string(), "libx265".to_string()];
+            if let Some(profile) = parameters.profile.as_deref().and_then(x265_profile) {
+                args.extend(["-profile:v".to_string(), profile.to_string()]);
+            }
+            if let Some(level) = level {
+                args.extend(["-x265-params".to_string(), format!("level-idc={:.1}", level as f64 / 30.0)]);
+            }
+            args
+        }
+        _ => return None,
+    };
+
+    if let Some(pix_fmt) = &parameters.pix_fmt {
+        args.extend(["-pix_fmt".to_string(), pix_fmt.clone()]);
+    }
+
+    if matches!(output_extension, "mp4" | "m4v" | "mov")
+        && let Some(timescale) = parameters.timescale()
+    {
+        args.extend(["-video_track_timescale".to_string(), timescale.to_string()]);
+    }
+
+    Some(args)
+}
+
+fn smart_render_encode_command(options: &ExportOptions, range: TimeRange, encoder_args: &[String], output: &Path) -> FfmpegCommand {
+    let mut ffmpeg_command = FfmpegCommand::new_with_path(ffmpeg_path());
+    ffmpeg_command.seek(range.start.to_string().as_str());
+    ffmpeg_command.to(range.end.to_string().as_str());
+    ffmpeg_command
+        .input(&options.input_path)
+        .overwrite()
+        .map("0:v:0")
+        .no_audio()
+        .args(encoder_args);
+
+    let encoder = EncoderFamily::X26x;
+
+    if let Some(rate_control) = &options.rate_control {
+        ffmpeg_command.args(encoder.rate_control_args(rate_control));
+    }
+
+    ffmpeg_command
+        .args(encoder.preset_args(options.encoder_preset.as_deref(), options.encoder_tune.as_deref()))
+        .output(output.to_string_lossy());
+    ffmpeg_command
+}
+
+fn x264_profile(profile: &str) -> Option<&'static str> {
+    match profile {
+        "Baseline" | "Constrained Baseline" => Some("baseline"),
+        "Main" => Some("main"),
+        "High" => Some("high"),
+        "High 10" => Some("high10"),
+        "High 4:2:2" => Some("high422"),
+        "High 4:4:4 Predictive" => Some("high444"),
+        _ => None,
+    }
+}
+
+fn x265_profile(profile: &str) -> Option<&'static str> {
+    match profile {
+        "Main" => Some("main"),
+        "Main 10" => Some("main10"),
+        "Main Still Picture" => Some("mainstillpicture"),
+        _ => None,
+    }
+}
+
+/// Filter trimming `segments` out of the video labeled `input`, e.g. `[0:v]`, and joining them into `[v]`,
+/// with `crossfade` seconds long fades between them if it's not zero.
+/// `post_filters` are appended after the join, e.g. `,scale=1280:720`.
+fn video_segments_filter(input: &str, segments: &[TimeRange], crossfade: f64, post_filters: &str) -> String {
+    if segments.len() <= 1 {
+        return format!("{}setpts=PTS-STARTPTS{}[v]", input, post_filters);
+    }
+
+    let split = format!(
+        "{}split={}{};",
+        input,
+        segments.len(),
+        (0..segments.len()).map(|i| format!("[vs{}]", i)).collect::<String>()
+    );
+
+    let trims = segments
+        .iter()
+        .enumerate()
+        .map(|(i, s)| format!("[vs{}]trim=start={}:end={},setpts=PTS-STARTPTS[vt{}];", i, s.start, s.end, i))
+        .collect::<String>();
+
+    if crossfade > 0.0 {
+        let mut joins = String::new();
+        let mut offset = 0.0;
+        let mut last = "[vt0]".to_string();
+        for i in 1..segments.len() {
+            offset += segments[i - 1].duration() - crossfade;
+            let output = format!("[vx{}]", i);
+            joins += &format!(
+                "{}[vt{}]xfade=transition=fade:duration={}:offset={}{};",
+                last, i, crossfade, offset, output
+            );
+            last = output;
+        }
+
+        return format!("{}{}{}{}null{}[v]", split, trims, joins, last, post_filters);
+    }
+
+    format!(
+        "{}{}{}concat=n={}:v=1:a=0{}[v]",
+        split,
+        trims,
+        (0..segments.len()).map(|i| format!("[vt{}]", i)).collect::<String>(),
+        segments.len(),
+        post_filters
+    )
+}
+
+/// Filter trimming `segments` out of the active audio streams of input `input_index`,
+/// with `crossfade` seconds long fades between them if it's not zero.
+/// Streams are mixed into `[a]` or kept as separate `[a0]`, `[a1]`.. tracks, see [`audio_output_labels`].
+fn audio_segments_filter(options: &ExportOptions, input_index: usize, segments: &[TimeRange], crossfade: f64) -> String {
+    if options.active_audio_streams.is_empty() {
+        // Generate silence
+        return format!("aevalsrc=0:d={}[a]", output_duration(segments, crossfade));
+    }
+
+    if !options.separate_audio_tracks {
+        if segments.len() <= 1 {
+            return audio_mix_filter(&options.active_audio_streams, input_index, "a");
+        }
+
+        return format!(
+            "{};{}",
+            audio_mix_filter(&options.active_audio_streams, input_index, "am"),
+            audio_join_filter("am", "a", segments, crossfade)
+        );
+    }
+
+    options
+        .active_audio_streams
+        .iter()
+        .enumerate()
+        .map(|(n, stream)| {
+            let gain = format!("[{}:{}]volume={},asetpts=PTS-STARTPTS", input_index, stream.index, stream.gain);
+            if segments.len() <= 1 {
+                format!("{}[a{}]", gain, n)
+            } else {
+                format!(
+                    "{}[am{}];{}",
+                    gain,
+                    n,
+                    audio_join_filter(&format!("am{}", n), &format!("a{}", n), segments, crossfade)
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Filter trimming `segments` out of `[input]` and joining them into `[output]`
+fn audio_join_filter(input: &str, output: &str, segments: &[TimeRange], crossfade: f64) -> String {
+    let split = format!(
+        "[{}]asplit={}{};",
+        input,
+        segments.len(),
+        (0..segments.len()).map(|i| format!("[{}s{}]", output, i)).collect::<String>()
+    );
+
+    let trims = segments
+        .iter()
+        .enumerate()
+        .map(|(i, s)| format!("[{output}s{i}]atrim=start={}:end={},asetpts=PTS-STARTPTS[{output}t{i}];", s.start, s.end))
+        .collect::<String>();
+
+    let joins = if crossfade > 0.0 {
+        let mut joins = vec![];
+        let mut last = format!("[{}t0]", output);
+        for i in 1..segments.len() {
+            let join_output = if i == segments.len() - 1 {
+                format!("[{}]", output)
+            } else {
+                format!("[{}x{}]", output, i)
+            };
+            joins.push(format!("{}[{}t{}]acrossfade=d={}{}", last, output, i, crossfade, join_output));
+            last = join_output;
+        }
+        joins.join(";")
+    } else {
+        format!(
+            "{}concat=n={}:v=0:a=1[{}]",
+            (0..segments.len()).map(|i| format!("[{}t{}]", output, i)).collect::<String>(),
+            segments.len(),
+            output
+        )
+    };
+
+    format!("{}{}{}", split, trims, joins)
+}
+
+/// Labels of the audio outputs produced by [`audio_segments_filter`]
+fn audio_output_labels(options: &ExportOptions) -> Vec<String> {
+    if options.separate_audio_tracks && !options.active_audio_streams.is_empty() {
+        (0..options.active_audio_streams.len()).map(|n| format!("[a{}]", n)).collect()
+    } else {
+        vec!["[a]".to_string()]
+    }
+}
+
+/// Maps the audio outputs of [`audio_segments_filter`], separate tracks keep the title and language
+/// of their source stream in input `input_index`
+fn map_audio_outputs(ffmpeg_command: &mut FfmpegCommand, options: &ExportOptions, input_index: usize) {
+    for label in audio_output_labels(options) {
+        ffmpeg_command.map(label);
+    }
+
+    if options.separate_audio_tracks {
+        for (n, stream) in options.active_audio_streams.iter().enumerate() {
+            ffmpeg_command.args([format!("-map_metadata:s:a:{}", n), format!("{}:s:{}", input_index, stream.index)]);
+        }
+        ffmpeg_command.args(audio_disposition_args(options));
+    }
+}
+
+/// Marks the track of `default_audio_stream`, or the first one, as the default audio track
+fn audio_disposition_args(options: &ExportOptions) -> Vec<String> {
+    let default_stream = options
+        .default_audio_stream
+        .or_else(|| options.active_audio_streams.first().map(|stream| stream.index));
+
+    options
+        .active_audio_streams
+        .iter()
+        .enumerate()
+        .flat_map(|(n, stream)| {
+            let disposition = if Some(stream.index) == default_stream { "default" } else { "0" };
+            [format!("-disposition:a:{}", n), disposition.to_string()]
+        })
+        .collect()
+}
+
+/// Crossfade duration between segments clamped to half of the shortest segment,
+/// zero if crossfades are not requested or not possible
+fn crossfade_duration(options: &ExportOptions, segments: &[TimeRange], gpu_frames: bool) -> f64 {
+    let Some(crossfade) = options.crossfade_duration.filter(|d| *d > 0.0) else {
+        return 0.0;
+    };
+
+    if segments.len() <= 1 {
+        return 0.0;
+    }
+
+    if gpu_frames {
+        warn!("Crossfades are not supported with GPU acceleration");
+        return 0.0;
+    }
+
+    let shortest = segments.iter().map(TimeRange::duration).fold(f64::INFINITY, f64::min);
+    crossfade.min(shortest / 2.0)
+}
+
+/// Crossfade of the re-encode of `segments` with the GPU decoding resolved for the input
+fn reencode_crossfade_duration(options: &ExportOptions, segments: &[TimeRange]) -> Result<f64, ProbeError> {
+    let probe = probe_media_blocking(&options.input_path)?;
+    Ok(crossfade_duration(options, segments, gpu_decoding(options, &probe).is_some()))
+}
+
+/// Hardware decoding arguments, decoder and scale filter of the input, `None` if it's decoded on the CPU
+fn gpu_decoding(options: &ExportOptions, probe: &MediaProbe) -> Option<(&'static str, &'static str, &'static str)> {
+    let Some(GpuAcceleration::Nvidia) = options.gpu_acceleration else {
+        return None;
+    };
+
+    let decoder = match probe.main_video_stream().and_then(|s| s.codec_name.as_deref()) {
+        Some("h264") => "h264_cuvid",
+        Some("hevc") => "hevc_cuvid",
+        Some("av1") => "av1_cuvid",
+        _ => return None,
+    };
+
+    if options.burn_in_subtitles.is_some() {
+        warn!("GPU decoding is not used when subtitles are burned in");
+        return None;
+    }
+
+    Some(("-hwaccel cuda -hwaccel_output_format cuda", decoder, "scale_cuda"))
+}
+
+/// Output duration of joined `segments`, each crossfade overlaps two of them
+fn output_duration(segments: &[TimeRange], crossfade: f64) -> f64 {
+    TimeRange::total_duration(segments) - crossfade * segments.len().saturating_sub(1) as f64
+}
+
+/// Filter mixing `audio_streams` of input `input_index` into `[output]`
+fn audio_mix_filter(audio_streams: &[ExportAudioStreamOptions], input_index: usize, output: &str) -> String {
+    let audio_streams_trim = audio_streams
+        .iter()
+        .map(|stream| {
+            format!(
+                "[{}:{}]volume={},asetpts=PTS-STARTPTS[a{}_{}];",
+                input_index, stream.index, stream.gain, input_index, stream.index
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("");
+
+    let audio_streams_labels = audio_streams
+        .iter()
+        .map(|stream| format!("[a{}_{}]", input_index, stream.index))
+        .collect::<Vec<_>>()
+        .join("");
+
+    format!(
+        "{}{}amix=inputs={}[{}]",
+        audio_streams_trim,
+        audio_streams_labels,
+        audio_streams.len(),
+        output
+    )
+}
+
+/// Start of the first and end of the last of the sorted `segments`
+fn segments_bounds(segments: &[TimeRange]) -> Result<(f64, f64), FfmpegTaskError> {
+    match (segments.first(), segments.last()) {
+        (Some(first), Some(last)) => Ok((first.start, last.end)),
+        _ => Err(FfmpegTaskError::InvalidOptions("Nothing to export".to_string())),
+    }
+}
+
+fn relative_to(segments: &[TimeRange], origin: f64) -> Vec<TimeRange> {
+    segments
+        .iter()
+        .map(|s| TimeRange {
+            start: s.start - origin,
+            end: s.end - origin,
+        })
+        .collect()
+}
+
+/// Path and contents of the concat demuxer list of `files`, written by the plan before its first step.
+/// `durations` replace the durations of the files, which otherwise end with their last packet.
+fn concat_list_file(temp_prefix: &str, files: &[PathBuf], durations: Option<&[f64]>) -> io::Result<(PathBuf, String)> {
+    let concat_list_path = temp_dir()?.join(format!("{}_concat.txt", temp_prefix));
+    let concat_list = files
+        .iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let mut entry = format!("file '{}'\n", path.to_string_lossy().replace('\'', "'\\''"));
+            if let Some(duration) = durations.and_then(|durations| durations.get(i)) {
+                entry += &format!("duration {}\n", duration);
+            }
+            entry
+        })
+        .collect::<String>();
+
+    Ok((concat_list_path, concat_list))
+}
+
+/// `subtitle_streams` of the export the output container can store
+fn subtitle_outputs(options: &ExportOptions) -> Vec<SubtitleOutput> {
+    if options.subtitle_streams.is_empty() {
+        return vec![];
+    }
+
+    let probe = match probe_media_blocking(&options.input_path) {
+        Ok(probe) => probe,
+        Err(e) => {
+            warn!("Subtitles are not exported: {e}");
+            return vec![];
+        }
+    };
+
+    let output_extension = options.output_extension();
+
+    options
+        .subtitle_streams
+        .iter()
+        .filter_map(|&index| {
+            let Some((_, stream)) = probe.subtitle_stream(index) else {
+                warn!("Stream {} of {} is not a subtitle stream", index, options.input_path);
+                return None;
+            };
+
+            let codec = output_subtitle_codec(&output_extension, stream);
+            if codec.is_none() {
+                warn!(
+                    "{} subtitles of stream {} can't be stored in {} files",
+                    stream.codec_name.as_deref().unwrap_or("Unknown"),
+                    index,
+                    output_extension
+                );
+            }
+
+            Some(SubtitleOutput { index, codec: codec? })
+        })
+        .collect()
+}
+
+/// Subtitle encoder for `stream` in the output container, `None` if the container can't store it
+fn output_subtitle_codec(output_extension: &str, stream: &MediaStream) -> Option<&'static str> {
+    match output_extension {
+        "mp4" | "m4v" | "mov" => (!stream.is_bitmap_subtitle()).then_some("mov_text"),
+        "webm" => (!stream.is_bitmap_subtitle()).then_some("webvtt"),
+        "mkv" | "mka" if stream.codec_name.as_deref() == Some("mov_text") => Some("srt"),
+        "mkv" | "mka" => Some("copy"),
+        // MPEG program and transport streams only store their own bitmap subtitles
+        "mpg" | "mpeg" | "vob" => (stream.codec_name.as_deref() == Some("dvd_subtitle")).then_some("copy"),
+        "ts" | "m2ts" => (stream.codec_name.as_deref() == Some("dvb_subtitle")).then_some("copy"),
+        // AVI, ASF, FLV and F4V have no subtitle streams
+        _ => None,
+    }
+}
+
+/// Maps `subtitle_outputs` of the first input
+fn map_subtitle_outputs(ffmpeg_command: &mut FfmpegCommand, subtitle_outputs: &[SubtitleOutput]) {
+    for (n, output) in subtitle_outputs.iter().enumerate() {
+        ffmpeg_command
+            .map(format!("0:{}", output.index))
+            .args([format!("-c:s:{}", n), output.codec.to_string()]);
+    }
+}
+
+/// [`subtitle_track`] of a re-encode, shifted by the crossfades the same way as the video
+fn reencode_subtitle_track(options: &ExportOptions) -> Result<Option<SubtitleTrack>, FfmpegTaskError> {
+    let segments = options.segments();
+    let crossfade = reencode_crossfade_duration(options, &segments)?;
+    Ok(subtitle_track(options, &segments, crossfade, &temp_file_prefix())?)
+}
+
+/// Cuts the exported subtitle streams to `segments` and joins the pieces, `None` if there is nothing to keep
+fn subtitle_track(options: &ExportOptions, segments: &[TimeRange], crossfade: f64, temp_prefix: &str) -> io::Result<Option<SubtitleTrack>> {
+    let subtitle_outputs = subtitle_outputs(options);
+    if subtitle_outputs.is_empty() {
+        return Ok(None);
+    }
+
+    let output_extension = options.output_extension();
+
+    let mut steps = vec![];
+    let mut temp_files = vec![];
+
+    for (i, segment) in segments.iter().enumerate() {
+        let path = temp_dir()?.join(format!("{}_subtitles_{}.{}", temp_prefix, i, output_extension));
+
+        let mut ffmpeg_command = FfmpegCommand::new_with_path(ffmpeg_path());
+        ffmpeg_command.seek(segment.start.to_string().as_str());
+        ffmpeg_command.to(segment.end.to_string().as_str());
+        ffmpeg_command.input(&options.input_path).overwrite();
+        map_subtitle_outputs(&mut ffmpeg_command, &subtitle_outputs);
+        ffmpeg_command.output(path.to_string_lossy());
+
+        // Copying subtitles takes no time compared to the video, so it doesn't count towards the progress
+        steps.push(ExportStep {
+            command: ffmpeg_command,
+            duration: 0.0,
+        });
+        temp_files.push(path);
+    }
+
+    if let [path] = &temp_files[..] {
+        let path = path.clone();
+        return Ok(Some(SubtitleTrack {
+            steps,
+            temp_files,
+            temp_file_contents: vec![],
+            path,
+        }));
+    }
+
+    // Joined pieces are as long as the segments they were cut from, overlapped by crossfades
+    let durations = segments.iter().map(|s| s.duration() - crossfade).collect::<Vec<_>>();
+    let (concat_list_path, concat_list) = concat_list_file(&format!("{}_subtitles", temp_prefix), &temp_files, Some(&durations))?;
+    let path = temp_dir()?.join(format!("{}_subtitles.{}", temp_prefix, output_extension));
+
+    let mut ffmpeg_command = FfmpegCommand::new_with_path(ffmpeg_path());
+    ffmpeg_command
+        .format("concat")
+        .args(["-safe", "0"])
+        .input(concat_list_path.to_string_lossy())
+        .overwrite()
+        .map("0")
+        .args(["-c", "copy"])
+        .output(path.to_string_lossy());
+    steps.push(ExportStep {
+        command: ffmpeg_command,
+        duration: 0.0,
+    });
+
+    temp_files.push(concat_list_path.clone());
+    temp_files.push(path.clone());
+
+    Ok(Some(SubtitleTrack {
+        steps,
+        temp_files,
+        temp_file_contents: vec![(concat_list_path, concat_list)],
+        path,
+    }))
+}
+
+/// Filter rendering `subtitles` onto the video of input 0 into `[vsub]`, ending with `;`.
+/// Text subtitles are timed from the start of the source, so frames are shifted back by `seek_start` while they are rendered.
+fn burn_in_subtitles_filter(
+    options: &ExportOptions,
+    subtitles: &BurnInSubtitles,
+    probe: &MediaProbe,
+    seek_start: f64,
+) -> Result<String, FfmpegTaskError> {
+    let subtitles_filter = match subtitles {
+        BurnInSubtitles::Stream { index } => {
+            let Some((position, stream)) = probe.subtitle_stream(*index) else {
+                return Err(FfmpegTaskError::InvalidOptions(format!(
+                    "Stream {} of {} is not a subtitle stream",
+                    index, options.input_path
+                )));
+            };
+
+            // Bitmap subtitles are decoded as a video stream seeked together with the input
+            if stream.is_bitmap_subtitle() {
+                return Ok(format!("[0:v][0:s:{}]overlay[vsub];", position));
+            }
+
+            format!("subtitles=filename={}:si={}", escape_filter_path(&options.input_path), position)
+        }
+        BurnInSubtitles::File { path } => format!("subtitles=filename={}", escape_filter_path(path)),
+    };
+
+    Ok(format!(
+        "[0:v]setpts=PTS+{}/TB,{},setpts=PTS-STARTPTS[vsub];",
+        seek_start, subtitles_filter
+    ))
+}
+
+/// Escapes `path` as a filter option value inside of a filtergraph
+fn escape_filter_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+
+    let escape = |value: &str, special: &[char]| {
+        value
+            .chars()
+            .flat_map(|c| special.contains(&c).then_some('\\').into_iter().chain([c]))
+            .collect::<String>()
+    };
+
+    let option_value = escape(path.as_str(), &['\\', ':', '\'']);
+    escape(&option_value, &['\\', '\'', '[', ']', ',', ';'])
+}
+
+/// [`chapters_metadata`] of a re-encode, shifted by the crossfades the same way as the video
+fn reencode_chapters_metadata(options: &ExportOptions) -> Result<Option<ChaptersMetadata>, FfmpegTaskError> {
+    let segments = options.segments();
+    let crossfade = reencode_crossfade_duration(options, &segments)?;
+    Ok(chapters_metadata(options, &segments, crossfade, &temp_file_prefix())?)
+}
+
+/// Export chapters rebased to `segments` as ffmetadata, `None` if the output has no chapters
+fn chapters_metadata(options: &ExportOptions, segments: &[TimeRange], crossfade: f64, temp_prefix: &str) -> io::Result<Option<ChaptersMetadata>> {
+    let chapters = export_chapters(options, segments, crossfade);
+    if chapters.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(ChaptersMetadata {
+        path: temp_dir()?.join(format!("{}_chapters.txt", temp_prefix)),
+        contents: chapters_ffmetadata(&chapters),
+    }))
+}
+
+/// Adds `chapters_metadata` as input `input_index` and takes the output chapters from it,
+/// without it the output has no chapters instead of the untrimmed chapters of the first input
+fn input_chapters(ffmpeg_command: &mut FfmpegCommand, chapters_metadata: Option<&ChaptersMetadata>, input_index: usize) {
+    match chapters_metadata {
+        Some(chapters_metadata) => {
+            ffmpeg_command
+                .format("ffmetadata")
+                .input(chapters_metadata.path.to_string_lossy())
+                .args(["-map_chapters", input_index.to_string().as_str()]);
+        }
+        None => {
+            ffmpeg_command.args(["-map_chapters", "-1"]);
+        }
+    }
+}
+
+/// Keyframes around the cut points of `segments`, taken from the keyframe index if the editor already built it.
+/// The whole video is scanned if a cut point has no keyframe within `KEYFRAME_SEARCH_WINDOW`
+fn keyframes_around(options: &ExportOptions, segments: &[TimeRange]) -> Result<Vec<f64>, FfmpegTaskError> {
+    if let Some(keyframes) = indexed_video_keyframes(&options.input_path) {
+        return Ok(keyframes.as_ref().clone());
+    }
+
+    let start_time = probe_media_blocking(&options.input_path)?.format.start_time.unwrap_or_default();
+    let keyframes = scan_video_keyframes_blocking(&options.input_path, Some(&keyframes_read_intervals(segments, start_time)))?;
+
+    let all_cuts_covered = segments
+        .iter()
+        .flat_map(|s| [s.start, s.end])
+        .all(|cut| snap_to_nearest_keyframe(&keyframes, cut).is_some_and(|k| (k - cut).abs() <= KEYFRAME_SEARCH_WINDOW));
+    if all_cuts_covered {
+        return Ok(keyframes);
+    }
+
+    warn!("No keyframes within {}s of a cut point, scanning the whole video", KEYFRAME_SEARCH_WINDOW);
+    Ok(scan_video_keyframes_blocking(&options.input_path, None)?)
+}
+
+/// ffprobe intervals around the cut points of `segments`, ffprobe seeks to timestamps including `start_time`
+fn keyframes_read_intervals(segments: &[TimeRange], start_time: f64) -> String {
+    segments
+        .iter()
+        .flat_map(|s| [s.start + start_time, s.end + start_time])
+        .map(|t| format!("{}%{}", (t - KEYFRAME_SEARCH_WINDOW).max(0.0), t + KEYFRAME_SEARCH_WINDOW))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn snap_to_nearest_keyframe(keyframes: &[f64], time: f64) -> Option<f64> {
+    keyframes.iter().copied().min_by(|a, b| (a - time).abs().total_cmp(&(b - time).abs()))
+}
+
+fn temp_dir() -> io::Result<PathBuf> {
+    let tmp_folder = std::env::temp_dir().join(APP_IDENTIFIER);
+    std::fs::create_dir_all(&tmp_folder)?;
+    Ok(tmp_folder)
+}
+
+/// Unique prefix for intermediate files of a single export
+fn temp_file_prefix() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or_default();
+    format!("export_{}", nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export_options(output_path: &str) -> ExportOptions {
+        serde_json::from_value(serde_json::json!({
+            "startTime": 0.0,
+            "endTime": 0.0,
+            "inputPath": "input.mp4",
+            "outputPath": output_path,
+            "activeAudioStreams": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn snaps_to_nearest_keyframe() {
+        let keyframes = [0.0, 2.0, 4.5, 10.0];
+
+        assert_eq!(snap_to_nearest_keyframe(&keyframes, 0.9), Some(0.0));
+        assert_eq!(snap_to_nearest_keyframe(&keyframes, 1.2), Some(2.0));
+        assert_eq!(snap_to_nearest_keyframe(&keyframes, 4.5), Some(4.5));
+        assert_eq!(snap_to_nearest_keyframe(&keyframes, 30.0), Some(10.0));
+        assert_eq!(snap_to_nearest_keyframe(&[], 1.0), None);
+    }
+
+    #[test]
+    fn keyframe_read_intervals_include_start_time() {
+        let segments = [TimeRange { start: 10.0, end: 50.0 }];
+
+        assert_eq!(keyframes_read_intervals(&segments, 1.5), "0%41.5,21.5%81.5");
+    }
+
+    #[test]
+    fn crossfade_is_clamped_to_half_of_the_shortest_segment() {
+        let mut options = export_options("output.mp4");
+        options.crossfade_duration = Some(2.0);
+        let segments = [TimeRange { start: 0.0, end: 10.0 }, TimeRange { start: 20.0, end: 23.0 }];
+
+        assert_eq!(crossfade_duration(&options, &segments, false), 1.5);
+        assert_eq!(crossfade_duration(&options, &segments, true), 0.0);
+        assert_eq!(crossfade_duration(&options, &segments[..1], false), 0.0);
+        assert_eq!(output_duration(&segments, 1.5), 11.5);
+    }
+
+    #[test]
+    fn parses_bitrates_into_kbps() {
+        assert_eq!(bitrate_kbps("128k"), Some(128.0));
+        assert_eq!(bitrate_kbps("2.5M"), Some(2500.0));
+        assert_eq!(bitrate_kbps("96000"), Some(96.0));
+        assert_eq!(bitrate_kbps("fast"), None);
+    }
+
+    fn subtitle_stream(codec_name: &str) -> MediaStream {
+        serde_json::from_value(serde_json::json!({
+            "index": 2,
+            "kind": "subtitle",
+            "codecName": codec_name,
+            "disposition": crate::ffprobe::MediaStreamDisposition::default(),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn subtitle_codec_depends_on_the_output_container() {
+        let (text, bitmap) = (subtitle_stream("subrip"), subtitle_stream("hdmv_pgs_subtitle"));
+
+        assert_eq!(output_subtitle_codec("mp4", &text), Some("mov_text"));
+        assert_eq!(output_subtitle_codec("mp4", &bitmap), None);
+        assert_eq!(output_subtitle_codec("webm", &text), Some("webvtt"));
+        assert_eq!(output_subtitle_codec("mkv", &subtitle_stream("mov_text")), Some("srt"));
+        assert_eq!(output_subtitle_codec("mkv", &bitmap), Some("copy"));
+        assert_eq!(output_subtitle_codec("ts", &subtitle_stream("dvb_subtitle")), Some("copy"));
+        assert_eq!(output_subtitle_codec("ts", &text), None);
+        assert_eq!(output_subtitle_codec("avi", &text), None);
+    }
+
+    #[test]
+    fn escapes_filter_paths() {
+        assert_eq!(escape_filter_path(r"C:\Videos\it's [1].srt"), r"C\\:/Videos/it\\\'s \[1\].srt");
+    }
+}
@@ -1,6 +1,13 @@
-use crate::ffmpeg::{FfmpegTasksQueue, cancel_ffmpeg_task, enqueue_export_video_task};
+use crate::ffmpeg::{
+    FfmpegTask, FfmpegTaskMove, FfmpegTasksQueue, cancel_ffmpeg_task, duplicate_ffmpeg_task, emit_ffmpeg_task_changed, enqueue_concat_videos_task,
+    enqueue_export_video_task, ffmpeg_queue_snapshot, find_ffmpeg_task, move_ffmpeg_task, pause_ffmpeg_task, remove_ffmpeg_task,
+    remove_finished_ffmpeg_tasks, resume_ffmpeg_task, retry_ffmpeg_task, set_ffmpeg_task_priority,
+};
+use crate::ffmpeg_encoder::validate_audio_options;
+use crate::ffmpeg_queue_store::save_ffmpeg_queue;
 use log::info;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 use tauri::Manager;
 
 #[derive(Serialize, Deserialize, Debug, Clone, ts_rs::TS)]
@@ -16,6 +23,200 @@ pub struct ExportOptions {
     pub frame_rate: Option<f64>,
     pub active_audio_streams: Vec<ExportAudioStreamOptions>,
     pub gpu_acceleration: Option<GpuAcceleration>,
+    #[serde(default)]
+    pub mode: ExportMode,
+    /// Ranges to keep and join, `start_time`..`end_time` is used when empty
+    #[serde(default)]
+    pub segments: Vec<TimeRange>,
+    /// Ranges to cut out, the rest of the video is kept
+    #[serde(default)]
+    pub removed_segments: Vec<TimeRange>,
+    /// Duration of crossfades between joined segments in seconds
+    #[serde(default)]
+    pub crossfade_duration: Option<f64>,
+    /// Output size limit, the video bitrate is calculated from it and `bitrate` is ignored
+    #[serde(default)]
+    pub target_size_bytes: Option<u64>,
+    /// Video rate control, `bitrate` is used as average bitrate when not set
+    #[serde(default)]
+    pub rate_control: Option<RateControl>,
+    /// x264 style preset name (`ultrafast`..`veryslow`) mapped to the selected encoder or an encoder specific preset
+    #[serde(default)]
+    pub encoder_preset: Option<String>,
+    #[serde(default)]
+    pub encoder_tune: Option<String>,
+    /// ffmpeg audio encoder, e.g. `aac` or `libopus`, the container default when not set
+    #[serde(default)]
+    pub audio_codec: Option<String>,
+    #[serde(default)]
+    pub audio_bitrate: Option<String>,
+    #[serde(default)]
+    pub audio_sample_rate: Option<u32>,
+    #[serde(default)]
+    pub audio_channels: Option<u32>,
+    /// Replace audio options the output container or codec can't handle instead of rejecting the export
+    #[serde(default)]
+    pub fix_incompatible_audio: bool,
+    /// Keep each active audio stream as its own output track instead of mixing them into one
+    #[serde(default)]
+    pub separate_audio_tracks: bool,
+    /// Source index of the stream whose track is marked default, the first active stream when not set
+    #[serde(default)]
+    pub default_audio_stream: Option<usize>,
+    /// Source indices of subtitle streams kept in the output, shifted to the exported ranges
+    #[serde(default)]
+    pub subtitle_streams: Vec<usize>,
+    /// Subtitles rendered into the video, needs re-encoding
+    #[serde(default)]
+    pub burn_in_subtitles: Option<BurnInSubtitles>,
+    /// Chapters written to the output, rebased to the exported ranges
+    #[serde(default)]
+    pub chapters: ExportChapters,
+}
+
+impl ExportOptions {
+    /// Lowercase output file extension, `mkv` if there is none
+    pub fn output_extension(&self) -> String {
+        Path::new(&self.output_path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_else(|| "mkv".to_string())
+    }
+
+    /// Ranges to keep sorted by start time with overlapping ones merged
+    pub fn segments(&self) -> Vec<TimeRange> {
+        let mut segments: Vec<TimeRange> = if self.segments.is_empty() {
+            vec![TimeRange {
+                start: self.start_time,
+                end: self.end_time,
+            }]
+        } else {
+            self.segments.clone()
+        };
+
+        segments.retain(|s| s.end > s.start);
+        segments.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+        let mut merged: Vec<TimeRange> = Vec::with_capacity(segments.len());
+        for segment in segments {
+            match merged.last_mut() {
+                Some(last) if segment.start <= last.end => last.end = last.end.max(segment.end),
+                _ => merged.push(segment),
+            }
+        }
+
+        merged
+    }
+
+    /// Replaces `segments` with what's left of them after cutting out `removed_segments`.
+    /// `start_time`..`end_time` clamped to the probed `duration` is used when no segments are set.
+    /// Returns `false` if nothing is left.
+    pub fn resolve_removed_segments(&mut self, duration: f64) -> bool {
+        if self.removed_segments.is_empty() {
+            return true;
+        }
+
+        let kept = if self.segments.is_empty() {
+            let end = if self.end_time > self.start_time {
+                self.end_time.min(duration)
+            } else {
+                duration
+            };
+            vec![TimeRange {
+                start: self.start_time.max(0.0),
+                end,
+            }]
+        } else {
+            self.segments()
+        };
+
+        let mut removed = self.removed_segments.clone();
+        removed.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+        let mut segments = vec![];
+        for range in kept {
+            let mut start = range.start;
+            for cut in removed.iter().filter(|cut| cut.end > range.start && cut.start < range.end) {
+                if cut.start > start {
+                    segments.push(TimeRange { start, end: cut.start });
+                }
+                start = start.max(cut.end);
+            }
+            if range.end > start {
+                segments.push(TimeRange { start, end: range.end });
+            }
+        }
+
+        self.removed_segments.clear();
+        self.segments = segments;
+
+        !self.segments.is_empty()
+    }
+}
+
+/// Video rate control, `quality` is CRF for x264/x265/SVT-AV1 and CQ for NVENC
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ts_rs::TS)]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase", tag = "type")]
+pub enum RateControl {
+    ConstantQuality {
+        quality: u32,
+    },
+    AverageBitrate {
+        bitrate: String,
+    },
+    ConstrainedVbr {
+        bitrate: String,
+        max_bitrate: String,
+        buffer_size: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl TimeRange {
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+
+    pub fn total_duration(ranges: &[TimeRange]) -> f64 {
+        ranges.iter().map(TimeRange::duration).sum()
+    }
+}
+
+/// Options of joining clips of one or several files into a single video
+#[derive(Serialize, Deserialize, Debug, Clone, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+pub struct ConcatOptions {
+    pub clips: Vec<ConcatClip>,
+    pub output_path: String,
+    /// Common resolution of the output, e.g. `1920:1080`, the first clip resolution is used if not set
+    pub resolution: Option<String>,
+    /// Common frame rate of the output, the first clip frame rate is used if not set
+    pub frame_rate: Option<f64>,
+    /// Common audio sample rate of the output, 48 kHz is used if not set
+    pub sample_rate: Option<u32>,
+    pub bitrate: Option<String>,
+    pub video_codec: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+pub struct ConcatClip {
+    pub input_path: String,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub active_audio_streams: Vec<ExportAudioStreamOptions>,
+}
+
+impl ConcatClip {
+    pub fn duration(&self) -> f64 {
+        self.end_time - self.start_time
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, ts_rs::TS)]
@@ -25,28 +226,309 @@ pub struct ExportAudioStreamOptions {
     pub gain: f64,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ts_rs::TS)]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase", tag = "type")]
+pub enum BurnInSubtitles {
+    /// Subtitle stream of the input by its source index
+    Stream { index: usize },
+    /// External subtitle file, e.g. `.srt` or `.ass`, timed from the start of the input
+    File { path: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default, ts_rs::TS)]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase", tag = "type")]
+pub enum ExportChapters {
+    /// Chapters of the input clipped to the exported ranges
+    #[default]
+    Source,
+    /// Chapters starting at each marker and lasting until the next one
+    Markers { markers: Vec<ChapterMarker> },
+    /// No chapters in the output
+    Remove,
+}
+
+/// Chapter start in the input timeline
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterMarker {
+    pub time: f64,
+    pub title: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, ts_rs::TS)]
 #[serde(rename_all = "camelCase")]
 pub enum GpuAcceleration {
     Nvidia,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportMode {
+    /// Decode, filter and encode the trimmed range
+    #[default]
+    Reencode,
+    /// Remux with `-c copy`, cut points are snapped to the nearest keyframes
+    StreamCopy,
+    /// Frame accurate cut re-encoding only the partial GOPs at the cut points
+    SmartRender,
+}
+
 #[tauri::command]
-pub async fn ffmpeg_export(app_handle: tauri::AppHandle, options: ExportOptions) {
-    println!("ffmpeg_export called, {:?}", options);
+pub async fn ffmpeg_export(app_handle: tauri::AppHandle, options: ExportOptions) -> Result<(), String> {
+    info!("ffmpeg_export called, {:?}", options);
+
+    let mut options = options;
+    if options.mode != ExportMode::StreamCopy {
+        validate_audio_options(&mut options)?;
+    }
+
+    if let Some(BurnInSubtitles::File { path }) = &options.burn_in_subtitles {
+        if !app_handle.asset_protocol_scope().is_allowed(path) {
+            return Err(format!("Access to {path} is not allowed"));
+        }
+        if !Path::new(path).is_file() {
+            return Err(format!("Subtitle file {path} doesn't exist"));
+        }
+    }
 
     let ffmpeg_tasks_queue = app_handle.state::<FfmpegTasksQueue>();
 
     enqueue_export_video_task(&ffmpeg_tasks_queue, options).await;
+
+    Ok(())
+}
+
+/// Checks that there are clips to join and each of them has a non-empty range
+fn validate_concat_clips(clips: &[ConcatClip]) -> Result<(), String> {
+    if clips.is_empty() {
+        return Err("No clips to join".to_string());
+    }
+    match clips.iter().find(|clip| !(clip.start_time >= 0.0 && clip.start_time < clip.end_time)) {
+        Some(clip) => Err(format!(
+            "Clip {} has an invalid range {}..{}",
+            clip.input_path, clip.start_time, clip.end_time
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Joins the clips of `options` into one export. No view calls it yet, clip lists come from scripts
+/// and integrations invoking the command directly.
+#[tauri::command]
+pub async fn ffmpeg_concat(app_handle: tauri::AppHandle, options: ConcatOptions) -> Result<(), String> {
+    info!("ffmpeg_concat called, {:?}", options);
+
+    validate_concat_clips(&options.clips)?;
+    if let Some(clip) = options
+        .clips
+        .iter()
+        .find(|clip| !app_handle.asset_protocol_scope().is_allowed(&clip.input_path))
+    {
+        return Err(format!("Access to {} is not allowed", clip.input_path));
+    }
+
+    let ffmpeg_tasks_queue = app_handle.state::<FfmpegTasksQueue>();
+
+    enqueue_concat_videos_task(&ffmpeg_tasks_queue, options).await;
+
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn cancel_ffmpeg_task_by_index(app_handle: tauri::AppHandle, task_index: usize) {
+pub async fn cancel_ffmpeg_task_by_id(app_handle: tauri::AppHandle, task_id: u64) {
     let ffmpeg_tasks_queue = app_handle.state::<FfmpegTasksQueue>();
-    let ffmpeg_tasks_queue = ffmpeg_tasks_queue.lock().await;
-    let task = ffmpeg_tasks_queue.get(task_index);
+    let task = find_ffmpeg_task(&ffmpeg_tasks_queue.lock().await, task_id).await;
     info!("task to cancel: {:?}", task);
-    if let Some(task) = task {
-        cancel_ffmpeg_task(task).await;
+    if let Some((_, task)) = task
+        && cancel_ffmpeg_task(&task).await
+    {
+        save_ffmpeg_queue().await;
+        emit_ffmpeg_task_changed(&task).await;
+    }
+}
+
+#[tauri::command]
+pub async fn pause_ffmpeg_task_by_id(app_handle: tauri::AppHandle, task_id: u64) -> Result<(), String> {
+    let ffmpeg_tasks_queue = app_handle.state::<FfmpegTasksQueue>();
+    let (_, task) = find_ffmpeg_task(&ffmpeg_tasks_queue.lock().await, task_id)
+        .await
+        .ok_or_else(|| format!("Task {task_id} not found"))?;
+
+    pause_ffmpeg_task(&task).await?;
+
+    info!("Paused ffmpeg task {}", task_id);
+    emit_ffmpeg_task_changed(&task).await;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn resume_ffmpeg_task_by_id(app_handle: tauri::AppHandle, task_id: u64) -> Result<(), String> {
+    let ffmpeg_tasks_queue = app_handle.state::<FfmpegTasksQueue>();
+    let (_, task) = find_ffmpeg_task(&ffmpeg_tasks_queue.lock().await, task_id)
+        .await
+        .ok_or_else(|| format!("Task {task_id} not found"))?;
+
+    resume_ffmpeg_task(&task).await?;
+
+    info!("Resumed ffmpeg task {}", task_id);
+    emit_ffmpeg_task_changed(&task).await;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn move_ffmpeg_task_by_id(app_handle: tauri::AppHandle, task_id: u64, task_move: FfmpegTaskMove) {
+    let ffmpeg_tasks_queue = app_handle.state::<FfmpegTasksQueue>();
+    if move_ffmpeg_task(&ffmpeg_tasks_queue, task_id, task_move).await {
+        info!("Moved ffmpeg task {} {:?}", task_id, task_move);
+        save_ffmpeg_queue().await;
+    }
+}
+
+#[tauri::command]
+pub async fn set_ffmpeg_task_priority_by_id(app_handle: tauri::AppHandle, task_id: u64, priority: i32) {
+    let ffmpeg_tasks_queue = app_handle.state::<FfmpegTasksQueue>();
+    if set_ffmpeg_task_priority(&ffmpeg_tasks_queue, task_id, priority).await {
+        save_ffmpeg_queue().await;
+    }
+}
+
+#[tauri::command]
+pub async fn retry_ffmpeg_task_by_id(app_handle: tauri::AppHandle, task_id: u64) -> Result<(), String> {
+    let ffmpeg_tasks_queue = app_handle.state::<FfmpegTasksQueue>();
+    if !retry_ffmpeg_task(&ffmpeg_tasks_queue, task_id).await {
+        return Err(format!("Task {task_id} can't be retried"));
+    }
+
+    info!("Retrying ffmpeg task {}", task_id);
+    save_ffmpeg_queue().await;
+
+    Ok(())
+}
+
+/// Queues a copy of the task, `options` replace export options of the copied export task.
+/// Returns id of the new task.
+#[tauri::command]
+pub async fn duplicate_ffmpeg_task_by_id(app_handle: tauri::AppHandle, task_id: u64, options: Option<ExportOptions>) -> Result<u64, String> {
+    let mut options = options;
+    if let Some(options) = &mut options
+        && options.mode != ExportMode::StreamCopy
+    {
+        validate_audio_options(options)?;
+    }
+
+    let ffmpeg_tasks_queue = app_handle.state::<FfmpegTasksQueue>();
+    let new_task_id = duplicate_ffmpeg_task(&ffmpeg_tasks_queue, task_id, options)
+        .await
+        .ok_or_else(|| format!("Task {task_id} can't be duplicated"))?;
+
+    info!("Duplicated ffmpeg task {} as {}", task_id, new_task_id);
+
+    Ok(new_task_id)
+}
+
+#[tauri::command]
+pub async fn remove_ffmpeg_task_by_id(app_handle: tauri::AppHandle, task_id: u64) -> Result<(), String> {
+    let ffmpeg_tasks_queue = app_handle.state::<FfmpegTasksQueue>();
+    if !remove_ffmpeg_task(&ffmpeg_tasks_queue, task_id).await {
+        return Err(format!("Task {task_id} is not finished"));
+    }
+
+    info!("Removed ffmpeg task {}", task_id);
+    save_ffmpeg_queue().await;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn clear_finished_ffmpeg_tasks(app_handle: tauri::AppHandle) {
+    let ffmpeg_tasks_queue = app_handle.state::<FfmpegTasksQueue>();
+    let removed = remove_finished_ffmpeg_tasks(&ffmpeg_tasks_queue).await;
+
+    info!("Removed {} finished ffmpeg tasks", removed);
+    save_ffmpeg_queue().await;
+}
+
+/// Whole queue, after it the frontend is kept up to date by [`FfmpegTaskUpdate`](crate::ffmpeg::FfmpegTaskUpdate) events
+#[tauri::command]
+pub async fn get_ffmpeg_queue(app_handle: tauri::AppHandle) -> Vec<FfmpegTask> {
+    ffmpeg_queue_snapshot(&app_handle.state::<FfmpegTasksQueue>()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export_options(start_time: f64, end_time: f64) -> ExportOptions {
+        serde_json::from_value(serde_json::json!({
+            "startTime": start_time,
+            "endTime": end_time,
+            "inputPath": "input.mp4",
+            "outputPath": "output.mp4",
+            "activeAudioStreams": [],
+        }))
+        .unwrap()
+    }
+
+    fn ranges(ranges: &[(f64, f64)]) -> Vec<TimeRange> {
+        ranges.iter().map(|&(start, end)| TimeRange { start, end }).collect()
+    }
+
+    #[test]
+    fn segments_default_to_start_and_end_time() {
+        assert_eq!(export_options(2.0, 8.0).segments(), ranges(&[(2.0, 8.0)]));
+    }
+
+    #[test]
+    fn segments_are_sorted_and_merged() {
+        let mut options = export_options(0.0, 0.0);
+        options.segments = ranges(&[(10.0, 12.0), (1.0, 3.0), (2.0, 5.0), (6.0, 6.0), (5.0, 7.0)]);
+
+        assert_eq!(options.segments(), ranges(&[(1.0, 7.0), (10.0, 12.0)]));
+    }
+
+    #[test]
+    fn removed_segments_are_cut_from_the_whole_video() {
+        let mut options = export_options(0.0, 0.0);
+        options.removed_segments = ranges(&[(50.0, 70.0), (10.0, 20.0)]);
+
+        assert!(options.resolve_removed_segments(60.0));
+        assert_eq!(options.segments, ranges(&[(0.0, 10.0), (20.0, 50.0)]));
+        assert!(options.removed_segments.is_empty());
+    }
+
+    #[test]
+    fn removed_segments_are_cut_from_kept_segments() {
+        let mut options = export_options(0.0, 0.0);
+        options.segments = ranges(&[(0.0, 10.0), (20.0, 30.0)]);
+        options.removed_segments = ranges(&[(5.0, 25.0)]);
+
+        assert!(options.resolve_removed_segments(60.0));
+        assert_eq!(options.segments, ranges(&[(0.0, 5.0), (25.0, 30.0)]));
+    }
+
+    #[test]
+    fn removing_everything_leaves_nothing_to_export() {
+        let mut options = export_options(10.0, 20.0);
+        options.removed_segments = ranges(&[(0.0, 60.0)]);
+
+        assert!(!options.resolve_removed_segments(60.0));
+    }
+
+    #[test]
+    fn concat_clips_need_a_range() {
+        let clip = |start_time, end_time| ConcatClip {
+            input_path: "input.mp4".to_string(),
+            start_time,
+            end_time,
+            active_audio_streams: vec![],
+        };
+
+        assert!(validate_concat_clips(&[]).is_err());
+        assert!(validate_concat_clips(&[clip(0.0, 5.0), clip(2.0, 3.0)]).is_ok());
+        assert!(validate_concat_clips(&[clip(0.0, 5.0), clip(3.0, 3.0)]).is_err());
+        assert!(validate_concat_clips(&[clip(-1.0, 5.0)]).is_err());
+        assert!(validate_concat_clips(&[clip(f64::NAN, 5.0)]).is_err());
     }
 }
@@ -64,14 +64,3 @@ pub fn ffmpeg_is_installed() -> bool {
         .map(|s| s.success())
         .unwrap_or_else(|_| false)
 }
-
-pub fn ffprobe_is_installed() -> bool {
-    Command::new(ffprobe_path())
-        .arg("-version")
-        .create_no_window()
-        .stderr(Stdio::null())
-        .stdout(Stdio::null())
-        .status()
-        .map(|s| s.success())
-        .unwrap_or_else(|_| false)
-}
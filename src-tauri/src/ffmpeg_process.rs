@@ -0,0 +1,34 @@
+use std::io;
+
+/// Whether [`suspend_process`] can pause processes on this platform
+pub const SUSPEND_SUPPORTED: bool = cfg!(unix);
+
+/// Stops the process with `SIGSTOP` until [`resume_process`] is called
+#[cfg(unix)]
+pub fn suspend_process(pid: u32) -> io::Result<()> {
+    send_signal(pid, libc::SIGSTOP)
+}
+
+#[cfg(unix)]
+pub fn resume_process(pid: u32) -> io::Result<()> {
+    send_signal(pid, libc::SIGCONT)
+}
+
+#[cfg(unix)]
+fn send_signal(pid: u32, signal: libc::c_int) -> io::Result<()> {
+    // SAFETY: kill only sends a signal, pid belongs to a child that is not reaped yet
+    match unsafe { libc::kill(pid as libc::pid_t, signal) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn suspend_process(_pid: u32) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Pausing ffmpeg is only supported on Unix"))
+}
+
+#[cfg(not(unix))]
+pub fn resume_process(_pid: u32) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Pausing ffmpeg is only supported on Unix"))
+}
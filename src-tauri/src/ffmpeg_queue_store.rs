@@ -0,0 +1,244 @@
+use crate::APP_HANDLE;
+use crate::ffmpeg::{FfmpegTask, FfmpegTasksQueue, emit_ffmpeg_task_changed, emit_ffmpeg_tasks_removed, run_next_task};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+use tokio::fs;
+
+const FFMPEG_QUEUE_FILE_NAME: &str = "ffmpeg_queue.json";
+
+const DEFAULT_HISTORY_MAX_TASKS: usize = 100;
+const DEFAULT_HISTORY_MAX_AGE_DAYS: u32 = 30;
+
+const MILLIS_IN_DAY: u64 = 24 * 60 * 60 * 1000;
+
+/// How long finished, failed, cancelled and interrupted tasks are kept
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+pub struct FfmpegHistoryRetention {
+    pub max_tasks: Option<usize>,
+    pub max_age_days: Option<u32>,
+}
+
+impl Default for FfmpegHistoryRetention {
+    fn default() -> Self {
+        Self {
+            max_tasks: Some(DEFAULT_HISTORY_MAX_TASKS),
+            max_age_days: Some(DEFAULT_HISTORY_MAX_AGE_DAYS),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PersistedFfmpegQueue {
+    #[serde(default)]
+    history_retention: FfmpegHistoryRetention,
+    #[serde(default)]
+    tasks: Vec<FfmpegTask>,
+}
+
+pub struct FfmpegQueueStoreState {
+    history_retention: RwLock<FfmpegHistoryRetention>,
+}
+
+impl FfmpegQueueStoreState {
+    pub fn new() -> Self {
+        Self {
+            history_retention: RwLock::new(FfmpegHistoryRetention::default()),
+        }
+    }
+
+    fn history_retention(&self) -> FfmpegHistoryRetention {
+        *self.history_retention.read().unwrap()
+    }
+}
+
+pub fn unix_time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn ffmpeg_queue_file_path(app_handle: &AppHandle) -> Option<PathBuf> {
+    match app_handle.path().app_data_dir() {
+        Ok(dir) => Some(dir.join(FFMPEG_QUEUE_FILE_NAME)),
+        Err(e) => {
+            warn!("Failed to resolve app data dir: {e}");
+            None
+        }
+    }
+}
+
+/// Reads tasks saved by [`save_ffmpeg_queue`]. Their ids are reserved, so tasks created before
+/// [`restore_ffmpeg_queue`] don't collide with them, and their input files are allowed again,
+/// as the asset protocol scope only lasts for the session the files were opened in.
+pub async fn load_ffmpeg_queue(app_handle: &AppHandle) -> Option<PersistedFfmpegQueue> {
+    let path = ffmpeg_queue_file_path(app_handle)?;
+
+    let mut persisted: PersistedFfmpegQueue = match fs::read(&path).await {
+        Ok(content) => match serde_json::from_slice(&content) {
+            Ok(persisted) => persisted,
+            Err(e) => {
+                warn!("Failed to parse ffmpeg queue file {:?}: {e}", path);
+                return None;
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("Failed to read ffmpeg queue file {:?}: {e}", path);
+            return None;
+        }
+    };
+
+    let asset_protocol_scope = app_handle.asset_protocol_scope();
+    for task in &mut persisted.tasks {
+        task.restore_id();
+        for input_path in task.input_paths() {
+            if let Err(e) = asset_protocol_scope.allow_file(input_path) {
+                warn!("Failed to allow restored task input {input_path}: {e}");
+            }
+        }
+    }
+
+    Some(persisted)
+}
+
+/// Restores tasks read by [`load_ffmpeg_queue`], tasks which were in progress are marked interrupted
+pub async fn restore_ffmpeg_queue(app_handle: AppHandle, persisted: PersistedFfmpegQueue) {
+    info!("Restoring {} ffmpeg tasks", persisted.tasks.len());
+
+    *app_handle.state::<FfmpegQueueStoreState>().history_retention.write().unwrap() = persisted.history_retention;
+
+    let queue = app_handle.state::<FfmpegTasksQueue>();
+    let mut queue_guard = queue.lock().await;
+    let mut restored_tasks = vec![];
+    for mut task in persisted.tasks {
+        task.interrupt();
+        let task = Arc::new(tokio::sync::RwLock::new(task));
+        queue_guard.push(task.clone());
+        restored_tasks.push(task);
+    }
+    let pruned_task_ids = prune_history(&mut queue_guard, persisted.history_retention).await;
+    run_next_task(queue_guard).await;
+
+    for task in &restored_tasks {
+        if !pruned_task_ids.contains(&task.read().await.id()) {
+            emit_ffmpeg_task_changed(task).await;
+        }
+    }
+}
+
+/// Writes queued and finished tasks surviving a restart to the app data dir
+pub async fn save_ffmpeg_queue() {
+    let app_handle = APP_HANDLE.get().unwrap();
+    let Some(path) = ffmpeg_queue_file_path(app_handle) else {
+        return;
+    };
+
+    let history_retention = app_handle.state::<FfmpegQueueStoreState>().history_retention();
+
+    let queue = app_handle.state::<FfmpegTasksQueue>();
+    // Queue stays locked until the file is written, so concurrent saves don't interleave
+    let mut queue_guard = queue.lock().await;
+    let pruned_task_ids = prune_history(&mut queue_guard, history_retention).await;
+
+    let mut tasks = vec![];
+    for task in queue_guard.iter() {
+        let task = task.read().await;
+        if task.is_persistent() {
+            tasks.push(task.clone());
+        }
+    }
+
+    let persisted = PersistedFfmpegQueue { history_retention, tasks };
+
+    let content = match serde_json::to_vec_pretty(&persisted) {
+        Ok(content) => content,
+        Err(e) => {
+            warn!("Failed to serialize ffmpeg queue: {e}");
+            return;
+        }
+    };
+
+    if let Some(dir) = path.parent()
+        && let Err(e) = fs::create_dir_all(dir).await
+    {
+        warn!("Failed to create app data dir {:?}: {e}", dir);
+        return;
+    }
+
+    // Write to a temporary file first, so a crash while writing doesn't corrupt the saved queue
+    let temp_path = path.with_extension("json.tmp");
+    if let Err(e) = fs::write(&temp_path, content).await {
+        warn!("Failed to write ffmpeg queue file {:?}: {e}", temp_path);
+        return;
+    }
+    if let Err(e) = fs::rename(&temp_path, &path).await {
+        warn!("Failed to replace ffmpeg queue file {:?}: {e}", path);
+    }
+
+    drop(queue_guard);
+
+    emit_ffmpeg_tasks_removed(pruned_task_ids);
+}
+
+/// Removes history tasks older than `max_age_days` and all but the newest `max_tasks` of them,
+/// returns ids of the removed tasks
+async fn prune_history(queue: &mut Vec<Arc<tokio::sync::RwLock<FfmpegTask>>>, retention: FfmpegHistoryRetention) -> Vec<u64> {
+    let now = unix_time_millis();
+
+    let mut history = vec![];
+    for (index, task) in queue.iter().enumerate() {
+        if let Some(finished_at) = task.read().await.finished_at() {
+            history.push((index, finished_at));
+        }
+    }
+
+    let mut removed_indices = vec![];
+
+    if let Some(max_age_days) = retention.max_age_days {
+        let cutoff = now.saturating_sub(max_age_days as u64 * MILLIS_IN_DAY);
+        history.retain(|(index, finished_at)| {
+            let expired = *finished_at < cutoff;
+            if expired {
+                removed_indices.push(*index);
+            }
+            !expired
+        });
+    }
+
+    if let Some(max_tasks) = retention.max_tasks
+        && history.len() > max_tasks
+    {
+        history.sort_by_key(|(_, finished_at)| *finished_at);
+        let excess = history.len() - max_tasks;
+        removed_indices.extend(history.iter().take(excess).map(|(index, _)| *index));
+    }
+
+    removed_indices.sort_unstable();
+    let mut removed_task_ids = vec![];
+    for index in removed_indices.into_iter().rev() {
+        removed_task_ids.push(queue.remove(index).read().await.id());
+    }
+
+    removed_task_ids
+}
+
+#[tauri::command]
+pub fn get_ffmpeg_history_retention(app_handle: AppHandle) -> FfmpegHistoryRetention {
+    app_handle.state::<FfmpegQueueStoreState>().history_retention()
+}
+
+#[tauri::command]
+pub async fn set_ffmpeg_history_retention(app_handle: AppHandle, retention: FfmpegHistoryRetention) {
+    info!("Ffmpeg history retention set to {:?}", retention);
+
+    *app_handle.state::<FfmpegQueueStoreState>().history_retention.write().unwrap() = retention;
+
+    save_ffmpeg_queue().await;
+}
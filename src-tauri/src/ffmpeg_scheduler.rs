@@ -0,0 +1,82 @@
+use crate::ffmpeg::{FfmpegTasksQueue, run_next_task};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use tauri::Manager;
+
+const DEFAULT_INTERACTIVE_CONCURRENCY: usize = 2;
+const DEFAULT_EXPORT_CONCURRENCY: usize = 1;
+
+/// Group of tasks sharing concurrency slots
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum FfmpegTaskSlot {
+    /// Short jobs the user is waiting for, e.g. audio extraction of an opened file
+    Interactive,
+    /// Long running exports
+    Export,
+    /// Runs alone, nothing queued after it starts until it finished
+    Exclusive,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+pub struct FfmpegConcurrencyLimits {
+    pub interactive: usize,
+    pub export: usize,
+}
+
+impl Default for FfmpegConcurrencyLimits {
+    fn default() -> Self {
+        Self {
+            interactive: DEFAULT_INTERACTIVE_CONCURRENCY,
+            export: DEFAULT_EXPORT_CONCURRENCY,
+        }
+    }
+}
+
+impl FfmpegConcurrencyLimits {
+    pub fn limit(&self, slot: FfmpegTaskSlot) -> usize {
+        match slot {
+            FfmpegTaskSlot::Interactive => self.interactive,
+            FfmpegTaskSlot::Export => self.export,
+            FfmpegTaskSlot::Exclusive => 1,
+        }
+    }
+}
+
+pub struct FfmpegSchedulerState {
+    limits: RwLock<FfmpegConcurrencyLimits>,
+}
+
+impl FfmpegSchedulerState {
+    pub fn new() -> Self {
+        Self {
+            limits: RwLock::new(FfmpegConcurrencyLimits::default()),
+        }
+    }
+
+    pub fn limits(&self) -> FfmpegConcurrencyLimits {
+        *self.limits.read().unwrap()
+    }
+}
+
+#[tauri::command]
+pub fn get_ffmpeg_concurrency_limits(app_handle: tauri::AppHandle) -> FfmpegConcurrencyLimits {
+    app_handle.state::<FfmpegSchedulerState>().limits()
+}
+
+#[tauri::command]
+pub async fn set_ffmpeg_concurrency_limits(app_handle: tauri::AppHandle, limits: FfmpegConcurrencyLimits) {
+    // At least one slot of each kind, otherwise its tasks would never start
+    let limits = FfmpegConcurrencyLimits {
+        interactive: limits.interactive.max(1),
+        export: limits.export.max(1),
+    };
+    info!("Ffmpeg concurrency limits set to {:?}", limits);
+
+    *app_handle.state::<FfmpegSchedulerState>().limits.write().unwrap() = limits;
+
+    // Start tasks fitting into the new slots
+    let queue = app_handle.state::<FfmpegTasksQueue>();
+    run_next_task(queue.lock().await).await;
+}
@@ -0,0 +1,134 @@
+use crate::ffprobe::ProbeError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+pub enum FfmpegTaskErrorKind {
+    /// Input file is outside of the asset protocol scope
+    InputNotAllowed,
+    ProbeFailed,
+    InvalidOptions,
+    /// ffmpeg commands for the task could not be built
+    PrepareFailed,
+    FfmpegNotInstalled,
+    /// ffmpeg process could not be started or controlled
+    SpawnFailed,
+    /// ffmpeg exited with a non-zero status
+    FfmpegFailed,
+    OutputTooLarge,
+    DownloadFailed,
+    Cancelled,
+    Io,
+    Internal,
+}
+
+#[derive(Debug)]
+pub enum FfmpegTaskError {
+    InputNotAllowed(String),
+    Probe(ProbeError),
+    InvalidOptions(String),
+    Prepare(String),
+    FfmpegNotInstalled,
+    Spawn(std::io::Error),
+    /// ffmpeg stdin is not available, so the task could not be cancelled
+    Stdin,
+    /// ffmpeg output could not be read
+    Output(anyhow::Error),
+    Ffmpeg {
+        exit_status: String,
+        errors: Vec<String>,
+    },
+    OutputTooLarge {
+        size: u64,
+        target_size: u64,
+    },
+    Download(anyhow::Error),
+    Cancelled,
+    Io(std::io::Error),
+    Internal(String),
+}
+
+impl FfmpegTaskError {
+    pub fn kind(&self) -> FfmpegTaskErrorKind {
+        match self {
+            FfmpegTaskError::InputNotAllowed(_) => FfmpegTaskErrorKind::InputNotAllowed,
+            FfmpegTaskError::Probe(ProbeError::NotInstalled) => FfmpegTaskErrorKind::FfmpegNotInstalled,
+            FfmpegTaskError::Probe(_) => FfmpegTaskErrorKind::ProbeFailed,
+            FfmpegTaskError::InvalidOptions(_) => FfmpegTaskErrorKind::InvalidOptions,
+            FfmpegTaskError::Prepare(_) => FfmpegTaskErrorKind::PrepareFailed,
+            FfmpegTaskError::FfmpegNotInstalled => FfmpegTaskErrorKind::FfmpegNotInstalled,
+            FfmpegTaskError::Spawn(_) | FfmpegTaskError::Stdin | FfmpegTaskError::Output(_) => FfmpegTaskErrorKind::SpawnFailed,
+            FfmpegTaskError::Ffmpeg { .. } => FfmpegTaskErrorKind::FfmpegFailed,
+            FfmpegTaskError::OutputTooLarge { .. } => FfmpegTaskErrorKind::OutputTooLarge,
+            FfmpegTaskError::Download(_) => FfmpegTaskErrorKind::DownloadFailed,
+            FfmpegTaskError::Cancelled => FfmpegTaskErrorKind::Cancelled,
+            FfmpegTaskError::Io(_) => FfmpegTaskErrorKind::Io,
+            FfmpegTaskError::Internal(_) => FfmpegTaskErrorKind::Internal,
+        }
+    }
+}
+
+impl fmt::Display for FfmpegTaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FfmpegTaskError::InputNotAllowed(path) => write!(f, "Access to {path} is not allowed"),
+            FfmpegTaskError::Probe(e) => write!(f, "{e}"),
+            FfmpegTaskError::InvalidOptions(message) => write!(f, "{message}"),
+            FfmpegTaskError::Prepare(message) => write!(f, "{message}"),
+            FfmpegTaskError::FfmpegNotInstalled => write!(f, "FFmpeg is not installed"),
+            FfmpegTaskError::Spawn(e) => write!(f, "Failed to start ffmpeg: {e}"),
+            FfmpegTaskError::Stdin => write!(f, "Failed to open ffmpeg stdin"),
+            FfmpegTaskError::Output(e) => write!(f, "Failed to read ffmpeg output: {e}"),
+            FfmpegTaskError::Ffmpeg { exit_status, .. } => write!(f, "FFmpeg exited with {exit_status}"),
+            FfmpegTaskError::OutputTooLarge { size, target_size } => {
+                write!(f, "Output of {size} bytes doesn't fit into {target_size} bytes")
+            }
+            FfmpegTaskError::Download(e) => write!(f, "Failed to download ffmpeg: {e}"),
+            FfmpegTaskError::Cancelled => write!(f, "Task was cancelled"),
+            FfmpegTaskError::Io(e) => write!(f, "{e}"),
+            FfmpegTaskError::Internal(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for FfmpegTaskError {}
+
+impl From<std::io::Error> for FfmpegTaskError {
+    fn from(e: std::io::Error) -> Self {
+        FfmpegTaskError::Io(e)
+    }
+}
+
+impl From<ProbeError> for FfmpegTaskError {
+    fn from(e: ProbeError) -> Self {
+        FfmpegTaskError::Probe(e)
+    }
+}
+
+/// Reason of a failed task shown to the user
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+pub struct FfmpegTaskFailure {
+    pub kind: FfmpegTaskErrorKind,
+    pub message: String,
+    /// Last error lines printed by ffmpeg
+    pub ffmpeg_errors: Vec<String>,
+}
+
+impl From<FfmpegTaskError> for FfmpegTaskFailure {
+    fn from(e: FfmpegTaskError) -> Self {
+        let kind = e.kind();
+        let message = e.to_string();
+        let ffmpeg_errors = match e {
+            FfmpegTaskError::Ffmpeg { errors, .. } => errors,
+            _ => vec![],
+        };
+
+        Self {
+            kind,
+            message,
+            ffmpeg_errors,
+        }
+    }
+}
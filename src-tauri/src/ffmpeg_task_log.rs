@@ -0,0 +1,149 @@
+use crate::ffmpeg::{FfmpegTasksQueue, find_ffmpeg_task};
+use ffmpeg_sidecar::event::LogLevel;
+use log::info;
+use ringbuf::HeapRb;
+use ringbuf::traits::{Consumer, Observer, RingBuffer};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+
+/// How many output lines are kept for each ffmpeg run
+const FFMPEG_RUN_LOG_LINES: usize = 2000;
+
+/// How many last ffmpeg runs are kept for a task, export plans and retries run ffmpeg several times
+const FFMPEG_TASK_LOG_RUNS: usize = 20;
+
+/// Output of ffmpeg runs of a single task, kept apart from the app logs
+/// so a long export doesn't push everything else out of them
+#[derive(Default)]
+pub struct FfmpegTaskLog {
+    runs: VecDeque<FfmpegRunLog>,
+}
+
+struct FfmpegRunLog {
+    command: String,
+    lines: HeapRb<String>,
+    dropped_lines: usize,
+    exit_status: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FfmpegRunLogRecord {
+    command: String,
+    lines: Vec<String>,
+    /// Lines dropped from the start of the output to keep the log bounded
+    dropped_lines: usize,
+    exit_status: Option<String>,
+}
+
+pub type SharedFfmpegTaskLog = Arc<Mutex<FfmpegTaskLog>>;
+
+impl FfmpegTaskLog {
+    pub fn start_run(&mut self, command: String) {
+        if self.runs.len() == FFMPEG_TASK_LOG_RUNS {
+            self.runs.pop_front();
+        }
+
+        self.runs.push_back(FfmpegRunLog {
+            command,
+            lines: HeapRb::new(FFMPEG_RUN_LOG_LINES),
+            dropped_lines: 0,
+            exit_status: None,
+        });
+    }
+
+    pub fn push_line(&mut self, level: &LogLevel, line: &str) {
+        let Some(run) = self.runs.back_mut() else {
+            return;
+        };
+
+        let line = match level {
+            LogLevel::Warning => format!("[warning] {line}"),
+            LogLevel::Error => format!("[error] {line}"),
+            LogLevel::Fatal => format!("[fatal] {line}"),
+            LogLevel::Info | LogLevel::Unknown => line.to_string(),
+        };
+
+        if run.lines.is_full() {
+            run.dropped_lines += 1;
+        }
+        run.lines.push_overwrite(line);
+    }
+
+    pub fn finish_run(&mut self, exit_status: String) {
+        if let Some(run) = self.runs.back_mut() {
+            run.exit_status = Some(exit_status);
+        }
+    }
+
+    pub fn records(&self) -> Vec<FfmpegRunLogRecord> {
+        self.runs
+            .iter()
+            .map(|run| FfmpegRunLogRecord {
+                command: run.command.clone(),
+                lines: run.lines.iter().cloned().collect(),
+                dropped_lines: run.dropped_lines,
+                exit_status: run.exit_status.clone(),
+            })
+            .collect()
+    }
+
+    /// Plain text of the log to attach to bug reports
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+
+        for run in &self.runs {
+            let _ = writeln!(text, "$ {}", run.command);
+            if run.dropped_lines > 0 {
+                let _ = writeln!(text, "... {} earlier lines dropped", run.dropped_lines);
+            }
+            for line in run.lines.iter() {
+                let _ = writeln!(text, "{line}");
+            }
+            let _ = writeln!(text, "Exit status: {}\n", run.exit_status.as_deref().unwrap_or("still running"));
+        }
+
+        text
+    }
+}
+
+impl fmt::Debug for FfmpegTaskLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FfmpegTaskLog").field("runs", &self.runs.len()).finish()
+    }
+}
+
+async fn find_ffmpeg_task_log(app_handle: &AppHandle, task_id: u64) -> Result<SharedFfmpegTaskLog, String> {
+    let ffmpeg_tasks_queue = app_handle.state::<FfmpegTasksQueue>();
+    let (_, task) = find_ffmpeg_task(&ffmpeg_tasks_queue.lock().await, task_id)
+        .await
+        .ok_or_else(|| format!("Task {task_id} not found"))?;
+
+    Ok(task.read().await.log())
+}
+
+#[tauri::command]
+pub async fn get_ffmpeg_task_log(app_handle: AppHandle, task_id: u64) -> Result<Vec<FfmpegRunLogRecord>, String> {
+    let log = find_ffmpeg_task_log(&app_handle, task_id).await?;
+    let records = log.lock().unwrap().records();
+
+    Ok(records)
+}
+
+#[tauri::command]
+pub async fn save_ffmpeg_task_log(app_handle: AppHandle, task_id: u64, path: String) -> Result<(), String> {
+    let log = find_ffmpeg_task_log(&app_handle, task_id).await?;
+    let text = log.lock().unwrap().to_text();
+
+    tokio::fs::write(&path, text)
+        .await
+        .map_err(|e| format!("Failed to save log to {path}: {e}"))?;
+
+    info!("Saved log of ffmpeg task {} to {}", task_id, path);
+
+    Ok(())
+}
@@ -1,9 +1,32 @@
-use crate::ffmpeg_path::{ffprobe_is_installed, ffprobe_path};
-use log::info;
+use crate::APP_HANDLE;
+use crate::ffmpeg_path::ffprobe_path;
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::Manager;
+use tokio::sync::OnceCell;
 use ts_rs::TS;
 
+/// Subtitle codecs storing images instead of text, they can't be converted to text formats
+const BITMAP_SUBTITLE_CODECS: [&str; 4] = ["hdmv_pgs_subtitle", "dvd_subtitle", "dvb_subtitle", "xsub"];
+
+/// How long ffprobe may read a file before it is killed, files on network drives can be slow
+const FFPROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the keyframe index scan may take, it reads every packet of the file
+const KEYFRAME_INDEX_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Probed files kept in [`MEDIA_PROBE_CACHE`]
+const MEDIA_PROBE_CACHE_CAPACITY: usize = 64;
+
+/// Files kept in [`KEYFRAME_INDEX_CACHE`], an index of a long video holds a lot of timestamps
+const KEYFRAME_INDEX_CACHE_CAPACITY: usize = 8;
+
 pub(crate) trait BackgroundCommand {
     fn create_no_window(&mut self) -> &mut Self;
 }
@@ -25,98 +48,770 @@ pub struct StreamInfo {
     pub codec_name: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
-pub struct FfprobeFormat {
-    pub duration: String,
+pub struct VideoAudioStreamsInfo {
+    pub audio_streams: Vec<StreamInfo>,
+    pub duration: f64,
+}
+
+impl From<&MediaProbe> for VideoAudioStreamsInfo {
+    fn from(probe: &MediaProbe) -> Self {
+        Self {
+            audio_streams: probe
+                .streams_of_kind(MediaStreamKind::Audio)
+                .map(|s| StreamInfo {
+                    index: s.index,
+                    codec_name: s.codec_name.clone().unwrap_or_default(),
+                })
+                .collect(),
+            duration: probe.format.duration.unwrap_or(0.0),
+        }
+    }
+}
+
+/// Codec parameters of a video stream, used to encode segments that can be joined with other ones
+#[derive(Debug, Clone)]
+pub struct VideoCodecParameters {
+    pub codec_name: String,
+    pub profile: Option<String>,
+    pub level: Option<i32>,
+    pub pix_fmt: Option<String>,
+    pub time_base: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frame_rate: Option<f64>,
 }
 
-#[derive(Serialize, Deserialize)]
+impl VideoCodecParameters {
+    fn from_stream(stream: &MediaStream) -> Option<Self> {
+        Some(Self {
+            codec_name: stream.codec_name.clone()?,
+            profile: stream.profile.clone(),
+            level: stream.level,
+            pix_fmt: stream.pix_fmt.clone(),
+            time_base: stream.time_base.clone(),
+            width: stream.width,
+            height: stream.height,
+            frame_rate: stream.r_frame_rate,
+        })
+    }
+
+    /// Denominator of the stream timebase, e.g. `15360` for `1/15360`
+    pub fn timescale(&self) -> Option<u32> {
+        self.time_base.as_deref()?.split_once('/')?.1.parse().ok()
+    }
+}
+
+/// Parses ffprobe rationals like `30000/1001`, `0/0` is treated as unknown
+fn parse_rational(value: &str) -> Option<f64> {
+    let (num, den) = value.split_once('/')?;
+    let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+    (num > 0.0 && den > 0.0).then_some(num / den)
+}
+
+/// Codec parameters of the main video stream, for use in blocking task runners
+pub fn get_video_codec_parameters(path: impl AsRef<Path>) -> Option<VideoCodecParameters> {
+    let probe = probe_media_blocking(path).inspect_err(|e| warn!("{e}")).ok()?;
+    VideoCodecParameters::from_stream(probe.main_video_stream()?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
-pub struct FfprobeOutput {
-    pub streams: Vec<StreamInfo>,
-    pub format: FfprobeFormat,
+#[ts(export)]
+pub enum MediaStreamKind {
+    Video,
+    Audio,
+    Subtitle,
+    Data,
+    Attachment,
+    Unknown,
 }
 
-#[derive(Clone, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
-pub struct VideoAudioStreamsInfo {
-    pub audio_streams: Vec<StreamInfo>,
-    pub duration: f64,
+#[ts(export)]
+pub struct MediaStreamDisposition {
+    pub default: bool,
+    pub forced: bool,
+    pub dub: bool,
+    pub original: bool,
+    pub comment: bool,
+    pub hearing_impaired: bool,
+    pub visual_impaired: bool,
+    /// Cover art stored as a video stream
+    pub attached_pic: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MediaStream {
+    pub index: i32,
+    pub kind: MediaStreamKind,
+    pub codec_name: Option<String>,
+    pub profile: Option<String>,
+    pub level: Option<i32>,
+    pub time_base: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Base frame rate of the stream
+    pub r_frame_rate: Option<f64>,
+    /// Average frame rate, differs from `r_frame_rate` for variable frame rate video
+    pub avg_frame_rate: Option<f64>,
+    pub pix_fmt: Option<String>,
+    pub color_space: Option<String>,
+    pub color_transfer: Option<String>,
+    pub color_primaries: Option<String>,
+    pub color_range: Option<String>,
+    pub bit_depth: Option<u32>,
+    /// Rotation in degrees from the display matrix, counter-clockwise
+    pub rotation: Option<f64>,
+    #[ts(type = "number | null")]
+    pub bit_rate: Option<u64>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+    pub channel_layout: Option<String>,
+    pub duration: Option<f64>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub disposition: MediaStreamDisposition,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MediaFormat {
+    /// Comma separated demuxer names, e.g. `mov,mp4,m4a,3gp,3g2,mj2`
+    pub format_name: Option<String>,
+    pub format_long_name: Option<String>,
+    /// Timestamp of the first frame, ffmpeg cut times count from it
+    pub start_time: Option<f64>,
+    pub duration: Option<f64>,
+    #[ts(type = "number | null")]
+    pub size: Option<u64>,
+    #[ts(type = "number | null")]
+    pub bit_rate: Option<u64>,
+    #[ts(type = "Record<string, string>")]
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MediaChapter {
+    pub start: f64,
+    pub end: f64,
+    pub title: Option<String>,
 }
 
-impl VideoAudioStreamsInfo {
-    pub fn empty() -> Self {
+/// Everything the app needs to know about a media file
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MediaProbe {
+    pub format: MediaFormat,
+    pub streams: Vec<MediaStream>,
+    pub chapters: Vec<MediaChapter>,
+}
+
+impl MediaStream {
+    pub fn is_bitmap_subtitle(&self) -> bool {
+        self.kind == MediaStreamKind::Subtitle && self.codec_name.as_deref().is_some_and(|codec| BITMAP_SUBTITLE_CODECS.contains(&codec))
+    }
+}
+
+impl MediaProbe {
+    pub fn streams_of_kind(&self, kind: MediaStreamKind) -> impl Iterator<Item = &MediaStream> {
+        self.streams.iter().filter(move |s| s.kind == kind)
+    }
+
+    /// Subtitle stream by its source index with its position among subtitle streams, as used by `0:s:N` specifiers
+    pub fn subtitle_stream(&self, index: usize) -> Option<(usize, &MediaStream)> {
+        self.streams_of_kind(MediaStreamKind::Subtitle)
+            .enumerate()
+            .find(|(_, s)| s.index as usize == index)
+    }
+
+    /// First video stream that is not cover art
+    pub fn main_video_stream(&self) -> Option<&MediaStream> {
+        self.streams_of_kind(MediaStreamKind::Video).find(|s| !s.disposition.attached_pic)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawSideData {
+    rotation: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct RawStream {
+    index: i32,
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    profile: Option<String>,
+    level: Option<i32>,
+    time_base: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    r_frame_rate: Option<String>,
+    avg_frame_rate: Option<String>,
+    pix_fmt: Option<String>,
+    color_space: Option<String>,
+    color_transfer: Option<String>,
+    color_primaries: Option<String>,
+    color_range: Option<String>,
+    bits_per_raw_sample: Option<String>,
+    bit_rate: Option<String>,
+    sample_rate: Option<String>,
+    channels: Option<u32>,
+    channel_layout: Option<String>,
+    duration: Option<String>,
+    #[serde(default)]
+    disposition: HashMap<String, i32>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    #[serde(default)]
+    side_data_list: Vec<RawSideData>,
+}
+
+#[derive(Deserialize)]
+struct RawFormat {
+    format_name: Option<String>,
+    format_long_name: Option<String>,
+    start_time: Option<String>,
+    duration: Option<String>,
+    size: Option<String>,
+    bit_rate: Option<String>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct RawChapter {
+    start_time: String,
+    end_time: String,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct RawMediaProbe {
+    format: RawFormat,
+    #[serde(default)]
+    streams: Vec<RawStream>,
+    #[serde(default)]
+    chapters: Vec<RawChapter>,
+}
+
+/// Tag lookup ignoring case, Matroska files often use upper case tag names
+fn find_tag(tags: &HashMap<String, String>, name: &str) -> Option<String> {
+    tags.iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.clone())
+}
+
+/// Bit depth from pixel format names like `yuv420p10le`, 8 bit formats have no suffix
+fn pix_fmt_bit_depth(pix_fmt: &str) -> Option<u32> {
+    let name = pix_fmt.trim_end_matches("le").trim_end_matches("be");
+    let digits_start = name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    match &name[digits_start..] {
+        "" => Some(8),
+        digits if name[..digits_start].ends_with('p') => digits.parse().ok(),
+        _ => None,
+    }
+}
+
+impl From<RawStream> for MediaStream {
+    fn from(raw: RawStream) -> Self {
+        let kind = match raw.codec_type.as_deref() {
+            Some("video") => MediaStreamKind::Video,
+            Some("audio") => MediaStreamKind::Audio,
+            Some("subtitle") => MediaStreamKind::Subtitle,
+            Some("data") => MediaStreamKind::Data,
+            Some("attachment") => MediaStreamKind::Attachment,
+            _ => MediaStreamKind::Unknown,
+        };
+
+        let bit_depth = raw
+            .bits_per_raw_sample
+            .as_deref()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .or_else(|| {
+                raw.pix_fmt
+                    .as_deref()
+                    .filter(|_| kind == MediaStreamKind::Video)
+                    .and_then(pix_fmt_bit_depth)
+            });
+
+        // Older ffprobe versions report rotation as a tag instead of side data
+        let rotation = raw
+            .side_data_list
+            .iter()
+            .find_map(|side_data| side_data.rotation)
+            .or_else(|| find_tag(&raw.tags, "rotate").and_then(|v| v.parse::<f64>().ok()).map(|v| -v));
+
+        let flag = |name: &str| raw.disposition.get(name).is_some_and(|v| *v != 0);
+        let disposition = MediaStreamDisposition {
+            default: flag("default"),
+            forced: flag("forced"),
+            dub: flag("dub"),
+            original: flag("original"),
+            comment: flag("comment"),
+            hearing_impaired: flag("hearing_impaired"),
+            visual_impaired: flag("visual_impaired"),
+            attached_pic: flag("attached_pic"),
+        };
+
         Self {
-            audio_streams: vec![],
-            duration: 0.0,
+            index: raw.index,
+            kind,
+            codec_name: raw.codec_name,
+            profile: raw.profile,
+            level: raw.level,
+            time_base: raw.time_base,
+            width: raw.width,
+            height: raw.height,
+            r_frame_rate: raw.r_frame_rate.as_deref().and_then(parse_rational),
+            avg_frame_rate: raw.avg_frame_rate.as_deref().and_then(parse_rational),
+            pix_fmt: raw.pix_fmt,
+            color_space: raw.color_space,
+            color_transfer: raw.color_transfer,
+            color_primaries: raw.color_primaries,
+            color_range: raw.color_range,
+            bit_depth,
+            rotation,
+            bit_rate: raw.bit_rate.and_then(|v| v.parse().ok()),
+            sample_rate: raw.sample_rate.and_then(|v| v.parse().ok()),
+            channels: raw.channels,
+            channel_layout: raw.channel_layout,
+            duration: raw.duration.and_then(|v| v.parse().ok()),
+            language: find_tag(&raw.tags, "language").filter(|v| v != "und"),
+            title: find_tag(&raw.tags, "title"),
+            disposition,
         }
     }
 }
 
-fn parse_ffprobe_output(output: &str) -> Result<VideoAudioStreamsInfo, serde_json::Error> {
-    let res = serde_json::from_str(output);
-    let ffprobe_data: FfprobeOutput = res?;
-    let audio_streams: Vec<StreamInfo> = ffprobe_data.streams.into_iter().collect();
+impl From<RawMediaProbe> for MediaProbe {
+    fn from(raw: RawMediaProbe) -> Self {
+        let format = MediaFormat {
+            format_name: raw.format.format_name,
+            format_long_name: raw.format.format_long_name,
+            start_time: raw.format.start_time.and_then(|v| v.parse().ok()),
+            duration: raw.format.duration.and_then(|v| v.parse().ok()),
+            size: raw.format.size.and_then(|v| v.parse().ok()),
+            bit_rate: raw.format.bit_rate.and_then(|v| v.parse().ok()),
+            tags: raw.format.tags,
+        };
+
+        let chapters = raw
+            .chapters
+            .into_iter()
+            .filter_map(|chapter| {
+                Some(MediaChapter {
+                    start: chapter.start_time.parse().ok()?,
+                    end: chapter.end_time.parse().ok()?,
+                    title: find_tag(&chapter.tags, "title"),
+                })
+            })
+            .collect();
 
-    Ok(VideoAudioStreamsInfo {
-        audio_streams,
-        duration: ffprobe_data.format.duration.parse::<f64>().unwrap_or(0.0),
-    })
+        Self {
+            format,
+            streams: raw.streams.into_iter().map(MediaStream::from).collect(),
+            chapters,
+        }
+    }
 }
 
-pub fn get_video_audio_streams_info(path: impl AsRef<str>) -> Option<VideoAudioStreamsInfo> {
-    let ffprobe_path = ffprobe_path();
+fn parse_media_probe(output: &str) -> Result<MediaProbe, serde_json::Error> {
+    let raw: RawMediaProbe = serde_json::from_str(output)?;
+    Ok(raw.into())
+}
 
-    if !ffprobe_is_installed() {
-        return None;
+#[derive(Debug)]
+pub enum ProbeError {
+    NotInstalled,
+    /// ffprobe could not be started for another reason than a missing binary
+    Spawn(std::io::Error),
+    Timeout(String),
+    /// File can't be opened or ffprobe doesn't recognize it as media
+    Unreadable {
+        path: String,
+        reason: String,
+    },
+    InvalidJson {
+        path: String,
+        error: serde_json::Error,
+    },
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeError::NotInstalled => write!(f, "FFprobe is not installed"),
+            ProbeError::Spawn(e) => write!(f, "Failed to start ffprobe: {e}"),
+            ProbeError::Timeout(path) => write!(f, "FFprobe timed out reading {path}"),
+            ProbeError::Unreadable { path, reason } => write!(f, "Failed to read {path}: {reason}"),
+            ProbeError::InvalidJson { path, error } => write!(f, "Invalid ffprobe output for {path}: {error}"),
+        }
     }
+}
+
+impl std::error::Error for ProbeError {}
+
+/// Result of an ffprobe run, valid while the file keeps its size and modification time
+struct CachedProbe<T> {
+    size: u64,
+    modified: Option<SystemTime>,
+    last_used: Instant,
+    value: Arc<OnceCell<Arc<T>>>,
+}
+
+type ProbeCache<T> = LazyLock<Mutex<HashMap<PathBuf, CachedProbe<T>>>>;
+
+/// Probes by file path. Concurrent requests for the same file wait for a single ffprobe run.
+static MEDIA_PROBE_CACHE: ProbeCache<MediaProbe> = LazyLock::new(Default::default);
+
+/// Keyframe timestamps of the main video stream by file path
+static KEYFRAME_INDEX_CACHE: ProbeCache<Vec<f64>> = LazyLock::new(Default::default);
+
+/// Cell of the cached `path` probe, replaced with an empty one if the file changed.
+/// The least recently used probe is dropped when a new one would exceed `capacity`.
+async fn cached_probe_cell<T>(cache: &ProbeCache<T>, path: &Path, capacity: usize) -> Result<Arc<OnceCell<Arc<T>>>, ProbeError> {
+    let metadata = tokio::fs::metadata(path).await.map_err(|e| ProbeError::Unreadable {
+        path: path.display().to_string(),
+        reason: e.to_string(),
+    })?;
+    let (size, modified) = (metadata.len(), metadata.modified().ok());
+
+    let mut cache = cache.lock().unwrap();
+    let cell = match cache.get_mut(path) {
+        Some(cached) if cached.size == size && cached.modified == modified => {
+            cached.last_used = Instant::now();
+            cached.value.clone()
+        }
+        _ => {
+            if !cache.contains_key(path)
+                && cache.len() >= capacity
+                && let Some(oldest) = cache.iter().min_by_key(|(_, cached)| cached.last_used).map(|(path, _)| path.clone())
+            {
+                cache.remove(&oldest);
+            }
+
+            let cell = Arc::new(OnceCell::new());
+            cache.insert(
+                path.to_path_buf(),
+                CachedProbe {
+                    size,
+                    modified,
+                    last_used: Instant::now(),
+                    value: cell.clone(),
+                },
+            );
+            cell
+        }
+    };
+
+    Ok(cell)
+}
+
+/// Probes format, all streams and chapters of a media file
+pub async fn probe_media(path: impl AsRef<Path>) -> Result<Arc<MediaProbe>, ProbeError> {
+    let path = path.as_ref();
+    let probe = cached_probe_cell(&MEDIA_PROBE_CACHE, path, MEDIA_PROBE_CACHE_CAPACITY).await?;
+
+    // Failed probes leave the cell empty, so the next request runs ffprobe again
+    probe.get_or_try_init(|| run_ffprobe(path)).await.cloned()
+}
+
+/// [`probe_media`] for blocking task runners
+pub fn probe_media_blocking(path: impl AsRef<Path>) -> Result<Arc<MediaProbe>, ProbeError> {
+    tauri::async_runtime::block_on(probe_media(path))
+}
+
+async fn run_ffprobe(path: &Path) -> Result<Arc<MediaProbe>, ProbeError> {
+    let path_str = path.display().to_string();
+
+    let mut command = Command::new(ffprobe_path());
 
     #[rustfmt::skip]
-    let output = std::process::Command::new(ffprobe_path)
+    command
         .create_no_window()
         .args([
-            "-v", "quiet",
+            "-v", "error",
             "-print_format", "json",
-            "-select_streams", "a",
             "-show_format",
             "-show_streams",
-            path.as_ref()
+            "-show_chapters",
         ])
-        .output()
-        .ok()?;
+        .arg(path);
+
+    let output = ffprobe_output(command, path, FFPROBE_TIMEOUT).await?;
+
+    let probe = parse_media_probe(&output).map_err(|error| ProbeError::InvalidJson {
+        path: path_str.clone(),
+        error,
+    })?;
 
-    let output_str = String::from_utf8_lossy(&output.stdout);
+    debug!("Probed {}: {} streams", path_str, probe.streams.len());
 
-    parse_ffprobe_output(&output_str).ok()
+    Ok(Arc::new(probe))
 }
 
-pub fn get_video_streams_info(path: impl AsRef<str>) -> Option<FfprobeOutput> {
-    let ffprobe_path = ffprobe_path();
+/// Runs ffprobe `command` reading `path` and returns its stdout
+async fn ffprobe_output(command: Command, path: &Path, timeout: Duration) -> Result<String, ProbeError> {
+    let path_str = path.display().to_string();
 
-    info!("Using ffprobe: {:?}", ffprobe_path);
+    let mut command = tokio::process::Command::from(command);
+    command.kill_on_drop(true);
+
+    let output = match tokio::time::timeout(timeout, command.output()).await {
+        Err(_) => return Err(ProbeError::Timeout(path_str)),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => return Err(ProbeError::NotInstalled),
+        Ok(Err(e)) => return Err(ProbeError::Spawn(e)),
+        Ok(Ok(output)) => output,
+    };
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = match stderr.trim() {
+            "" => format!("ffprobe exited with {}", output.status),
+            stderr => stderr.to_string(),
+        };
+        return Err(ProbeError::Unreadable { path: path_str, reason });
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
 
-    if !ffprobe_is_installed() {
+/// Rejects files the user didn't open, the webview may only probe those
+fn check_path_allowed(path: &str) -> Result<(), String> {
+    if !APP_HANDLE.get().unwrap().asset_protocol_scope().is_allowed(path) {
+        return Err(format!("Access to {path} is not allowed"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn probe_media_file(path: String) -> Result<MediaProbe, String> {
+    check_path_allowed(&path)?;
+    let probe = probe_media(&path).await.map_err(|e| e.to_string())?;
+    Ok(probe.as_ref().clone())
+}
+
+/// Sorted keyframe timestamps (in seconds) of the main video stream from a scan of all its packets,
+/// cached the same way as [`probe_media`]. Empty if the file has no video.
+pub async fn video_keyframe_index(path: impl AsRef<Path>) -> Result<Arc<Vec<f64>>, ProbeError> {
+    let path = path.as_ref();
+    let keyframes = cached_probe_cell(&KEYFRAME_INDEX_CACHE, path, KEYFRAME_INDEX_CACHE_CAPACITY).await?;
+    keyframes
+        .get_or_try_init(|| async { scan_video_keyframes(path, None).await.map(Arc::new) })
+        .await
+        .cloned()
+}
+
+/// [`video_keyframe_index`] of `path` if it's already scanned and the file didn't change since
+pub fn indexed_video_keyframes(path: impl AsRef<Path>) -> Option<Arc<Vec<f64>>> {
+    let path = path.as_ref();
+    let metadata = std::fs::metadata(path).ok()?;
+
+    let cache = KEYFRAME_INDEX_CACHE.lock().unwrap();
+    let cached = cache.get(path)?;
+    if cached.size != metadata.len() || cached.modified != metadata.modified().ok() {
         return None;
     }
 
+    cached.value.get().cloned()
+}
+
+/// Sorted keyframe timestamps (in seconds) of the main video stream, empty if the file has no video.
+/// Timestamps count from the file start time like the cut times, `read_intervals` uses ffprobe `-read_intervals`
+/// syntax with timestamps including the start time, `None` scans the whole file.
+async fn scan_video_keyframes(path: &Path, read_intervals: Option<&str>) -> Result<Vec<f64>, ProbeError> {
+    let probe = probe_media(path).await?;
+    let Some(video_stream) = probe.main_video_stream() else {
+        return Ok(vec![]);
+    };
+
+    let started = Instant::now();
+    let stream_index = video_stream.index.to_string();
+    let start_time = probe.format.start_time.unwrap_or_default();
+
+    let mut command = Command::new(ffprobe_path());
+
+    // CSV is much smaller than JSON for files with millions of packets
     #[rustfmt::skip]
-    let output = std::process::Command::new(ffprobe_path)
+    command
         .create_no_window()
         .args([
-            "-v", "quiet",
-            "-print_format", "json",
-            "-select_streams", "v",
-            "-show_format",
-            "-show_streams",
-            path.as_ref()
-        ])
-        .output()
-        .ok()?;
+            "-v", "error",
+            "-select_streams", stream_index.as_str(),
+            "-show_entries", "packet=pts_time,flags",
+            "-print_format", "csv=print_section=0",
+        ]);
+
+    if let Some(read_intervals) = read_intervals {
+        command.args(["-read_intervals", read_intervals]);
+    }
+    command.arg(path);
 
-    let output_str = String::from_utf8_lossy(&output.stdout);
+    let timeout = if read_intervals.is_some() {
+        FFPROBE_TIMEOUT
+    } else {
+        KEYFRAME_INDEX_TIMEOUT
+    };
+    let output = ffprobe_output(command, path, timeout).await?;
 
-    let ffprobe_data: FfprobeOutput = serde_json::from_str(&output_str).ok()?;
+    let mut keyframes: Vec<f64> = output
+        .lines()
+        .filter_map(|line| {
+            let (pts_time, flags) = line.split_once(',')?;
+            if !flags.starts_with('K') {
+                return None;
+            }
+            pts_time.parse::<f64>().ok().map(|pts_time| pts_time - start_time)
+        })
+        .collect();
 
-    Some(ffprobe_data)
+    keyframes.sort_by(f64::total_cmp);
+    keyframes.dedup();
+
+    debug!("Read {} keyframes of {} in {:?}", keyframes.len(), path.display(), started.elapsed());
+
+    Ok(keyframes)
+}
+
+/// [`scan_video_keyframes`] for blocking task runners
+pub fn scan_video_keyframes_blocking(path: impl AsRef<Path>, read_intervals: Option<&str>) -> Result<Vec<f64>, ProbeError> {
+    tauri::async_runtime::block_on(scan_video_keyframes(path.as_ref(), read_intervals))
+}
+
+#[tauri::command]
+pub async fn get_video_keyframe_index(path: String) -> Result<Vec<f64>, String> {
+    check_path_allowed(&path)?;
+    let keyframes = video_keyframe_index(&path).await.map_err(|e| e.to_string())?;
+    Ok(keyframes.as_ref().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rationals() {
+        assert_eq!(parse_rational("25/1"), Some(25.0));
+        assert_eq!(parse_rational("30000/1001"), Some(30000.0 / 1001.0));
+        assert_eq!(parse_rational("0/0"), None);
+        assert_eq!(parse_rational("25"), None);
+    }
+
+    #[test]
+    fn bit_depth_from_pixel_format() {
+        assert_eq!(pix_fmt_bit_depth("yuv420p"), Some(8));
+        assert_eq!(pix_fmt_bit_depth("yuv420p10le"), Some(10));
+        assert_eq!(pix_fmt_bit_depth("yuv444p12be"), Some(12));
+        assert_eq!(pix_fmt_bit_depth("nv12"), None);
+    }
+
+    #[test]
+    fn parses_ffprobe_output() {
+        let output = r#"{
+            "format": {"format_name": "mov,mp4,m4a,3gp,3g2,mj2", "start_time": "0.021333", "duration": "12.500000"},
+            "streams": [
+                {
+                    "index": 0,
+                    "codec_type": "video",
+                    "codec_name": "hevc",
+                    "pix_fmt": "yuv420p10le",
+                    "r_frame_rate": "30000/1001",
+                    "avg_frame_rate": "0/0",
+                    "disposition": {"default": 1, "attached_pic": 0},
+                    "side_data_list": [{"rotation": -90}]
+                },
+                {
+                    "index": 1,
+                    "codec_type": "audio",
+                    "codec_name": "aac",
+                    "sample_rate": "48000",
+                    "channels": 2,
+                    "tags": {"LANGUAGE": "und", "title": "Commentary"}
+                }
+            ],
+            "chapters": [{"start_time": "0.000000", "end_time": "5.000000", "tags": {"TITLE": "Intro"}}]
+        }"#;
+
+        let probe = parse_media_probe(output).unwrap();
+
+        assert_eq!(probe.format.start_time, Some(0.021333));
+        assert_eq!(probe.format.duration, Some(12.5));
+
+        let video = probe.main_video_stream().unwrap();
+        assert_eq!(video.bit_depth, Some(10));
+        assert_eq!(video.r_frame_rate, Some(30000.0 / 1001.0));
+        assert_eq!(video.avg_frame_rate, None);
+        assert_eq!(video.rotation, Some(-90.0));
+        assert!(video.disposition.default);
+
+        let audio = probe.streams_of_kind(MediaStreamKind::Audio).next().unwrap();
+        assert_eq!(audio.sample_rate, Some(48000));
+        assert_eq!(audio.language, None);
+        assert_eq!(audio.title.as_deref(), Some("Commentary"));
+
+        assert_eq!(probe.chapters.len(), 1);
+        assert_eq!(probe.chapters[0].end, 5.0);
+        assert_eq!(probe.chapters[0].title.as_deref(), Some("Intro"));
+    }
+
+    fn test_files(dir_name: &str, names: &[&str]) -> Vec<PathBuf> {
+        let dir = std::env::temp_dir().join(dir_name);
+        std::fs::create_dir_all(&dir).unwrap();
+        names
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                std::fs::write(&path, name).unwrap();
+                path
+            })
+            .collect()
+    }
+
+    #[test]
+    fn probe_cache_drops_the_least_recently_used_file() {
+        let cache: ProbeCache<u32> = LazyLock::new(Default::default);
+        let paths = test_files("probe_cache_lru_test", &["a", "b", "c"]);
+
+        tauri::async_runtime::block_on(async {
+            let a = cached_probe_cell(&cache, &paths[0], 2).await.unwrap();
+            cached_probe_cell(&cache, &paths[1], 2).await.unwrap();
+            // Using `a` again leaves `b` as the least recently used one
+            let a_again = cached_probe_cell(&cache, &paths[0], 2).await.unwrap();
+            assert!(Arc::ptr_eq(&a, &a_again));
+            cached_probe_cell(&cache, &paths[2], 2).await.unwrap();
+        });
+
+        let cache = cache.lock().unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains_key(&paths[0]));
+        assert!(!cache.contains_key(&paths[1]));
+        assert!(cache.contains_key(&paths[2]));
+    }
+
+    #[test]
+    fn probe_cache_forgets_changed_files() {
+        let cache: ProbeCache<u32> = LazyLock::new(Default::default);
+        let paths = test_files("probe_cache_changed_test", &["a"]);
+
+        tauri::async_runtime::block_on(async {
+            let before = cached_probe_cell(&cache, &paths[0], 2).await.unwrap();
+            before.set(Arc::new(1)).unwrap();
+
+            std::fs::write(&paths[0], "changed").unwrap();
+            let after = cached_probe_cell(&cache, &paths[0], 2).await.unwrap();
+            assert!(!Arc::ptr_eq(&before, &after));
+            assert!(after.get().is_none());
+        });
+    }
 }
@@ -1,7 +1,15 @@
 mod ffmpeg;
+mod ffmpeg_chapters;
 mod ffmpeg_download;
+mod ffmpeg_encoder;
+mod ffmpeg_export;
 mod ffmpeg_export_command;
 mod ffmpeg_path;
+mod ffmpeg_process;
+mod ffmpeg_queue_store;
+mod ffmpeg_scheduler;
+mod ffmpeg_task_error;
+mod ffmpeg_task_log;
 mod ffmpeg_time_duration;
 mod ffprobe;
 mod handle_cli_args;
@@ -12,14 +20,24 @@ mod open_devtools_command;
 mod select_new_video_file_command;
 mod temp_cleanup;
 
-use crate::ffmpeg::{FfmpegTasksQueue, create_ffmpeg_tasks_queue, emit_ffmpeg_queue_status, enqueue_download_ffmpeg_task};
-use crate::ffmpeg_export_command::{cancel_ffmpeg_task_by_index, ffmpeg_export};
+use crate::ffmpeg::{FfmpegTasksQueue, create_ffmpeg_tasks_queue, enqueue_download_ffmpeg_task};
+use crate::ffmpeg_export_command::{
+    cancel_ffmpeg_task_by_id, clear_finished_ffmpeg_tasks, duplicate_ffmpeg_task_by_id, ffmpeg_concat, ffmpeg_export, get_ffmpeg_queue,
+    move_ffmpeg_task_by_id, pause_ffmpeg_task_by_id, remove_ffmpeg_task_by_id, resume_ffmpeg_task_by_id, retry_ffmpeg_task_by_id,
+    set_ffmpeg_task_priority_by_id,
+};
+use crate::ffmpeg_queue_store::{
+    FfmpegQueueStoreState, get_ffmpeg_history_retention, load_ffmpeg_queue, restore_ffmpeg_queue, set_ffmpeg_history_retention,
+};
+use crate::ffmpeg_scheduler::{FfmpegSchedulerState, get_ffmpeg_concurrency_limits, set_ffmpeg_concurrency_limits};
+use crate::ffmpeg_task_log::{get_ffmpeg_task_log, save_ffmpeg_task_log};
+use crate::ffprobe::{get_video_keyframe_index, probe_media_file};
 use crate::handle_cli_args::handle_cli_args_on_frontend_initialized;
 use crate::handle_main_window_event::handle_main_window_event;
 use crate::integrated_server::{IntegratedServerState, get_integrated_server_state, start_integrated_server};
 use crate::logs_store::{LogsStore, get_logs, get_logs_store_target};
 use crate::open_devtools_command::open_devtools;
-use crate::select_new_video_file_command::select_new_video_file;
+use crate::select_new_video_file_command::{select_new_video_file, select_subtitles_file};
 use crate::temp_cleanup::cleanup_temp;
 use std::env;
 use std::ops::Deref;
@@ -55,14 +73,20 @@ pub fn run() {
 
             let app_handle = app.handle().clone();
             async_runtime::spawn(async move {
+                // Restored ids are reserved before the download task takes the next one
+                let persisted = load_ffmpeg_queue(&app_handle).await;
+
                 let queue = app_handle.state::<FfmpegTasksQueue>();
+                // Download goes first, restored exports wait for it as it is exclusive
                 enqueue_download_ffmpeg_task(&queue).await;
+                if let Some(persisted) = persisted {
+                    restore_ffmpeg_queue(app_handle.clone(), persisted).await;
+                }
             });
 
             let app_handle = app.handle().clone();
             app.listen("frontend-initialized", move |_event| {
                 let app_handle = app_handle.clone();
-                async_runtime::spawn(emit_ffmpeg_queue_status());
                 async_runtime::spawn(handle_cli_args_on_frontend_initialized(app_handle));
             });
 
@@ -73,15 +97,36 @@ pub fn run() {
             Ok(())
         })
         .manage(create_ffmpeg_tasks_queue())
+        .manage(FfmpegSchedulerState::new())
+        .manage(FfmpegQueueStoreState::new())
         .manage(IntegratedServerState::new())
         .manage(LogsStore::new())
         .invoke_handler(generate_handler![
             select_new_video_file,
+            select_subtitles_file,
             ffmpeg_export,
+            ffmpeg_concat,
             get_integrated_server_state,
             get_logs,
             open_devtools,
-            cancel_ffmpeg_task_by_index,
+            get_ffmpeg_queue,
+            cancel_ffmpeg_task_by_id,
+            pause_ffmpeg_task_by_id,
+            resume_ffmpeg_task_by_id,
+            move_ffmpeg_task_by_id,
+            set_ffmpeg_task_priority_by_id,
+            retry_ffmpeg_task_by_id,
+            duplicate_ffmpeg_task_by_id,
+            remove_ffmpeg_task_by_id,
+            clear_finished_ffmpeg_tasks,
+            get_ffmpeg_concurrency_limits,
+            set_ffmpeg_concurrency_limits,
+            get_ffmpeg_history_retention,
+            set_ffmpeg_history_retention,
+            get_ffmpeg_task_log,
+            save_ffmpeg_task_log,
+            probe_media_file,
+            get_video_keyframe_index,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,8 +1,7 @@
 use crate::ffmpeg::{FfmpegTasksQueue, enqueue_extract_audio_task};
-use crate::ffprobe;
-use crate::ffprobe::VideoAudioStreamsInfo;
+use crate::ffprobe::{VideoAudioStreamsInfo, probe_media};
 use crate::integrated_server::IntegratedServerState;
-use log::error;
+use log::{error, warn};
 use serde::{Deserialize, Serialize};
 use tauri::{Emitter, Manager};
 use tauri_plugin_dialog::{DialogExt, FilePath};
@@ -21,6 +20,12 @@ pub struct AudioStreamFilePath {
     pub path: String,
 }
 
+#[derive(Serialize, Deserialize, TS, Clone, Debug)]
+pub struct SubtitleStreamFilePath {
+    pub index: i32,
+    pub path: String,
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Clone, Deserialize, Serialize, TS)]
 #[serde(rename_all = "camelCase", rename_all_fields = "camelCase", tag = "event")]
@@ -34,6 +39,14 @@ pub enum SelectNewVideoFileEvent {
         video_file: String,
         audio_streams: Vec<AudioStreamFilePath>,
     },
+    VideoSubtitleStreamsReady {
+        video_file: String,
+        subtitle_streams: Vec<SubtitleStreamFilePath>,
+    },
+    VideoFileProbeFailed {
+        video_file: String,
+        error: String,
+    },
 }
 
 #[tauri::command]
@@ -52,7 +65,21 @@ pub async fn select_new_video_file_inner(file_path: Option<FilePath>, app_handle
         app_handle.state::<IntegratedServerState>().allow_file(path.to_string()).await;
 
         let path = path.to_string();
-        let audio_steams = ffprobe::get_video_audio_streams_info(&path).unwrap_or(VideoAudioStreamsInfo::empty());
+        // The video still opens without stream info, e.g. while ffprobe is being downloaded
+        let audio_steams = match probe_media(&path).await {
+            Ok(probe) => VideoAudioStreamsInfo::from(probe.as_ref()),
+            Err(e) => {
+                error!("Failed to probe {}: {}", path, e);
+                send_select_new_video_file_event(
+                    &app_handle,
+                    SelectNewVideoFileEvent::VideoFileProbeFailed {
+                        video_file: path.clone(),
+                        error: e.to_string(),
+                    },
+                )?;
+                VideoAudioStreamsInfo::default()
+            }
+        };
         let selected_video_file = Some(SelectedVideoFile {
             path: path.clone(),
             audio_steams,
@@ -74,6 +101,13 @@ pub async fn select_new_video_file_inner(file_path: Option<FilePath>, app_handle
                 &app_handle,
                 SelectNewVideoFileEvent::VideoAudioSteamsReady {
                     audio_streams: result.audio_streams,
+                    video_file: path.clone(),
+                },
+            )?;
+            send_select_new_video_file_event(
+                &app_handle,
+                SelectNewVideoFileEvent::VideoSubtitleStreamsReady {
+                    subtitle_streams: result.subtitle_streams,
                     video_file: path,
                 },
             )?;
@@ -89,6 +123,31 @@ fn send_select_new_video_file_event(app_handle: &tauri::AppHandle, event: Select
     app_handle.emit("select-new-video-file-event", event)
 }
 
+/// Subtitle file extensions the subtitles filter can burn in
+const SUBTITLE_FILE_EXTENSIONS: [&str; 4] = ["srt", "ass", "ssa", "vtt"];
+
+/// Lets the user pick a subtitle file to burn in, exports only read files the user picked
+#[tauri::command]
+pub async fn select_subtitles_file(app_handle: tauri::AppHandle) -> Option<String> {
+    let (tx, rx) = oneshot::channel();
+
+    app_handle
+        .dialog()
+        .file()
+        .add_filter("Subtitles", &SUBTITLE_FILE_EXTENSIONS)
+        .pick_file(move |file_path| {
+            let _ = tx.send(file_path);
+        });
+
+    let path = rx.await.unwrap()?;
+    if let Err(e) = app_handle.asset_protocol_scope().allow_file(path.as_path()?) {
+        warn!("Failed to allow subtitle file {}: {e}", path);
+        return None;
+    }
+
+    Some(path.to_string())
+}
+
 async fn pick_file_async(app_handle: &tauri::AppHandle) -> Option<FilePath> {
     let (tx, rx) = oneshot::channel();
 
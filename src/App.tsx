@@ -1,7 +1,7 @@
 import {useEffect, useState} from "react";
 import {observer} from "mobx-react-lite";
 import AppStateStore, {AppStateStoreContext} from "./stores/AppStateStore.ts";
-import {Container, CssBaseline, ThemeProvider} from "@mui/material";
+import {Alert, Container, CssBaseline, Snackbar, ThemeProvider} from "@mui/material";
 import "@fontsource/roboto/300.css";
 import "@fontsource/roboto/400.css";
 import "@fontsource/roboto/500.css";
@@ -46,6 +46,19 @@ const App = observer(() => {
           </Container>
           <FfmpegTasksQueueView />
           <LogsView />
+          <Snackbar
+            open={appStateStore.fileProbeError != null}
+            anchorOrigin={{vertical: "top", horizontal: "center"}}
+            onClose={appStateStore.dismissFileProbeError}
+          >
+            <Alert
+              severity="warning"
+              variant="filled"
+              onClose={appStateStore.dismissFileProbeError}
+            >
+              {appStateStore.fileProbeError}
+            </Alert>
+          </Snackbar>
         </ThemeProvider>
       </AppStateStoreContext.Provider>
     </LogsStoreContext.Provider>
@@ -19,13 +19,25 @@ import {
 import ListIcon from "@mui/icons-material/List";
 import AudiotrackIcon from "@mui/icons-material/Audiotrack";
 import VideocamIcon from "@mui/icons-material/Videocam";
+import VideoLibraryIcon from "@mui/icons-material/VideoLibrary";
 import DownloadIcon from "@mui/icons-material/Download";
 import {FfmpegTask} from "../generated/bindings/FfmpegTask.ts";
+import {FfmpegTaskFailure} from "../generated/bindings/FfmpegTaskFailure.ts";
 import CircularProgressWithLabel from "./ui/CircularProgressWithLabel.tsx";
-import {blue, green, red, grey} from "@mui/material/colors";
+import formatDuration from "../lib/formatDuration.ts";
+import {amber, blue, green, red, grey, orange} from "@mui/material/colors";
 import {revealItemInDir} from "@tauri-apps/plugin-opener";
 import {LogsStoreContext} from "../stores/LogsStore.ts";
 import CloseIcon from "@mui/icons-material/Close";
+import ArrowUpwardIcon from "@mui/icons-material/ArrowUpward";
+import ArrowDownwardIcon from "@mui/icons-material/ArrowDownward";
+import VerticalAlignTopIcon from "@mui/icons-material/VerticalAlignTop";
+import ReplayIcon from "@mui/icons-material/Replay";
+import ContentCopyIcon from "@mui/icons-material/ContentCopy";
+import DeleteIcon from "@mui/icons-material/Delete";
+import DescriptionIcon from "@mui/icons-material/Description";
+import PauseIcon from "@mui/icons-material/Pause";
+import PlayArrowIcon from "@mui/icons-material/PlayArrow";
 
 const getFfmpegTaskLabel = (ffmpegTask: FfmpegTask | null) => {
   if (!ffmpegTask) return "No tasks running";
@@ -33,12 +45,41 @@ const getFfmpegTaskLabel = (ffmpegTask: FfmpegTask | null) => {
   return getTaskView(ffmpegTask).label;
 };
 
+const getFailureText = (reason: FfmpegTaskFailure) => {
+  const lastFfmpegError = reason.ffmpegErrors[reason.ffmpegErrors.length - 1];
+
+  return lastFfmpegError ? `${reason.message}: ${lastFfmpegError}` : reason.message;
+};
+
+const getProgressText = (ffmpegTask: FfmpegTask) => {
+  const status = ffmpegTask.status;
+  if (status.type == "inProgress") {
+    return [
+      status.speed != null && `${status.speed.toFixed(2)}x`,
+      status.fps != null && `${Math.round(status.fps)} fps`,
+      status.bitrateKbps != null && `${Math.round(status.bitrateKbps)} kbps`,
+      status.outputSize != null && `${(status.outputSize / 1024 / 1024).toFixed(1)} MB`,
+      status.etaSeconds != null && `${formatDuration(status.etaSeconds)} left`,
+    ]
+      .filter(Boolean)
+      .join(" · ");
+  }
+
+  if (ffmpegTask.elapsedMillis != null && status.type != "queued" && status.type != "paused") {
+    return `Took ${formatDuration(ffmpegTask.elapsedMillis / 1000)}`;
+  }
+
+  return "";
+};
+
 const taskStatusColors = {
   queued: undefined,
   inProgress: blue[500],
+  paused: amber[700],
   finished: green[500],
   failed: red[600],
   cancelled: grey[600],
+  interrupted: orange[700],
 };
 
 const getTaskView = (ffmpegTask: FfmpegTask) => {
@@ -48,9 +89,11 @@ const getTaskView = (ffmpegTask: FfmpegTask) => {
         label: {
           queued: "Audio preparation queued",
           inProgress: "Preparing audio",
+          paused: "Audio preparation paused",
           finished: "Audio prepared",
-          failed: "Audio preparation failed - see logs for more info",
+          failed: "Audio preparation failed",
           cancelled: "Audio preparation cancelled",
+          interrupted: "Audio preparation interrupted",
         }[ffmpegTask.status.type],
         secondary: `${ffmpegTask.taskType.videoFilePath}`,
         icon: <AudiotrackIcon />,
@@ -63,22 +106,43 @@ const getTaskView = (ffmpegTask: FfmpegTask) => {
         label: {
           queued: "Video export queued",
           inProgress: "Exporting video",
+          paused: "Video export paused",
           finished: "Video exported",
-          failed: "Video export failed - see logs for more info",
+          failed: "Video export failed",
           cancelled: "Video export cancelled",
+          interrupted: "Video export interrupted",
         }[ffmpegTask.status.type],
         secondary: outputPath,
         icon: <VideocamIcon />,
         onClick: () => revealItemInDir(outputPath),
       };
+    case "concatVideos":
+      const concatOutputPath = ffmpegTask.taskType.options.outputPath;
+
+      return {
+        label: {
+          queued: "Clips join queued",
+          inProgress: "Joining clips",
+          paused: "Clips join paused",
+          finished: "Clips joined",
+          failed: "Clips join failed",
+          cancelled: "Clips join cancelled",
+          interrupted: "Clips join interrupted",
+        }[ffmpegTask.status.type],
+        secondary: concatOutputPath,
+        icon: <VideoLibraryIcon />,
+        onClick: () => revealItemInDir(concatOutputPath),
+      };
     case "downloadFfmpeg":
       return {
         label: {
           queued: "Ffmpeg download queued",
           inProgress: "Downloading ffmpeg",
+          paused: "Ffmpeg download paused",
           finished: "FFmpeg downloaded",
-          failed: "FFmpeg download failed - see logs for more info",
+          failed: "FFmpeg download failed",
           cancelled: "Ffmpeg download cancelled",
+          interrupted: "Ffmpeg download interrupted",
         }[ffmpegTask.status.type],
         secondary: "",
         icon: <DownloadIcon />,
@@ -87,6 +151,16 @@ const getTaskView = (ffmpegTask: FfmpegTask) => {
   }
 };
 
+const isRetryable = (ffmpegTask: FfmpegTask) =>
+  ffmpegTask.status.type == "failed" ||
+  ffmpegTask.status.type == "cancelled" ||
+  ffmpegTask.status.type == "interrupted";
+
+const isFinished = (ffmpegTask: FfmpegTask) =>
+  ffmpegTask.status.type != "queued" &&
+  ffmpegTask.status.type != "inProgress" &&
+  ffmpegTask.status.type != "paused";
+
 const filterTask = (ffmpegTasks: FfmpegTask) =>
   ffmpegTasks.taskType.type != "downloadFfmpeg" || !ffmpegTasks.taskType.result?.already_installed;
 
@@ -121,7 +195,7 @@ const FfmpegTasksQueueView = observer(() => {
             <ListItemText primary={"No tasks has been queued"} />
           </ListItem>
         )}
-        {appStateStore.ffmpegTasksQueue.ffmpegTasks.slice().map((ffmpegTask, index) => {
+        {appStateStore.ffmpegTasksQueue.ffmpegTasks.slice().map((ffmpegTask) => {
           const ItemBody = (
             <>
               <ListItemAvatar>
@@ -131,7 +205,23 @@ const FfmpegTasksQueueView = observer(() => {
               </ListItemAvatar>
               <ListItemText
                 primary={getTaskView(ffmpegTask).label}
-                secondary={getTaskView(ffmpegTask).secondary}
+                secondary={
+                  ffmpegTask.status.type == "failed" ? (
+                    <>
+                      {getTaskView(ffmpegTask).secondary}
+                      <br />
+                      {getFailureText(ffmpegTask.status.reason)}
+                    </>
+                  ) : getProgressText(ffmpegTask) ? (
+                    <>
+                      {getTaskView(ffmpegTask).secondary}
+                      <br />
+                      {getProgressText(ffmpegTask)}
+                    </>
+                  ) : (
+                    getTaskView(ffmpegTask).secondary
+                  )
+                }
               />
             </>
           );
@@ -142,24 +232,96 @@ const FfmpegTasksQueueView = observer(() => {
                 padding: getTaskView(ffmpegTask).onClick ? 0 : 1,
                 display: filterTask(ffmpegTask) ? undefined : "none",
               }}
-              key={index}
+              key={ffmpegTask.id}
               secondaryAction={
                 <Stack direction={"row"} spacing={1}>
-                  {ffmpegTask.status.type == "inProgress" &&
+                  {(ffmpegTask.status.type == "inProgress" || ffmpegTask.status.type == "paused") &&
                     (ffmpegTask.status.progress != 0 ? (
                       <CircularProgressWithLabel value={ffmpegTask.status.progress * 100} />
                     ) : (
                       <CircularProgress size={30} />
                     ))}
-                  {ffmpegTask.status.type == "inProgress" && (
+                  {ffmpegTask.status.type == "inProgress" &&
+                    ffmpegTask.taskType.type != "downloadFfmpeg" && (
+                      <IconButton
+                        aria-label="pause"
+                        onClick={() => appStateStore.ffmpegTasksQueue.pauseTask(ffmpegTask.id)}
+                      >
+                        <PauseIcon />
+                      </IconButton>
+                    )}
+                  {ffmpegTask.status.type == "paused" && (
+                    <IconButton
+                      aria-label="resume"
+                      onClick={() => appStateStore.ffmpegTasksQueue.resumeTask(ffmpegTask.id)}
+                    >
+                      <PlayArrowIcon />
+                    </IconButton>
+                  )}
+                  {ffmpegTask.status.type == "queued" && (
+                    <>
+                      <IconButton
+                        aria-label="start first"
+                        onClick={() => appStateStore.ffmpegTasksQueue.moveTask(ffmpegTask.id, "front")}
+                      >
+                        <VerticalAlignTopIcon />
+                      </IconButton>
+                      <IconButton
+                        aria-label="start earlier"
+                        onClick={() => appStateStore.ffmpegTasksQueue.moveTask(ffmpegTask.id, "up")}
+                      >
+                        <ArrowUpwardIcon />
+                      </IconButton>
+                      <IconButton
+                        aria-label="start later"
+                        onClick={() => appStateStore.ffmpegTasksQueue.moveTask(ffmpegTask.id, "down")}
+                      >
+                        <ArrowDownwardIcon />
+                      </IconButton>
+                    </>
+                  )}
+                  {!isFinished(ffmpegTask) && (
                     <IconButton
                       edge="end"
-                      aria-label="delete"
-                      onClick={() => appStateStore.ffmpegTasksQueue.cancelTask(index)}
+                      aria-label="cancel"
+                      onClick={() => appStateStore.ffmpegTasksQueue.cancelTask(ffmpegTask.id)}
                     >
                       <CloseIcon />
                     </IconButton>
                   )}
+                  {isRetryable(ffmpegTask) && (
+                    <IconButton
+                      aria-label="retry"
+                      onClick={() => appStateStore.ffmpegTasksQueue.retryTask(ffmpegTask.id)}
+                    >
+                      <ReplayIcon />
+                    </IconButton>
+                  )}
+                  {isFinished(ffmpegTask) && ffmpegTask.taskType.type != "downloadFfmpeg" && (
+                    <IconButton
+                      aria-label="duplicate"
+                      onClick={() => appStateStore.ffmpegTasksQueue.duplicateTask(ffmpegTask.id)}
+                    >
+                      <ContentCopyIcon />
+                    </IconButton>
+                  )}
+                  {isFinished(ffmpegTask) && ffmpegTask.taskType.type != "downloadFfmpeg" && (
+                    <IconButton
+                      aria-label="save log"
+                      onClick={() => appStateStore.ffmpegTasksQueue.saveTaskLog(ffmpegTask.id)}
+                    >
+                      <DescriptionIcon />
+                    </IconButton>
+                  )}
+                  {isFinished(ffmpegTask) && (
+                    <IconButton
+                      edge="end"
+                      aria-label="remove"
+                      onClick={() => appStateStore.ffmpegTasksQueue.removeTask(ffmpegTask.id)}
+                    >
+                      <DeleteIcon />
+                    </IconButton>
+                  )}
                 </Stack>
               }
             >
@@ -179,6 +341,9 @@ const FfmpegTasksQueueView = observer(() => {
       </List>
       <Box>
         <Button onClick={() => logsStore.setLogsWindowOpen(true)}>Show logs</Button>
+        <Button onClick={() => appStateStore.ffmpegTasksQueue.clearFinishedTasks()}>
+          Clear finished
+        </Button>
       </Box>
     </Box>
   );
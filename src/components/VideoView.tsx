@@ -6,6 +6,7 @@ import {
   Box,
   Button,
   Checkbox,
+  Chip,
   CircularProgress,
   Dialog,
   DialogActions,
@@ -43,7 +44,10 @@ import PauseCircleIcon from "@mui/icons-material/PauseCircle";
 import FullscreenIcon from "@mui/icons-material/Fullscreen";
 import FullscreenExitIcon from "@mui/icons-material/FullscreenExit";
 import {save} from "@tauri-apps/plugin-dialog";
-import {GpuAcceleration} from "../generated";
+import {ExportChapters, GpuAcceleration, selectSubtitlesFile} from "../generated";
+import BookmarkIcon from "@mui/icons-material/Bookmark";
+import BookmarkAddIcon from "@mui/icons-material/BookmarkAdd";
+import KeyIcon from "@mui/icons-material/Key";
 import ReplayIcon from "@mui/icons-material/Replay";
 import VolumeDown from "@mui/icons-material/VolumeDown";
 import VolumeUp from "@mui/icons-material/VolumeUp";
@@ -256,6 +260,18 @@ const VideoView = observer(() => {
 
   const audioCtx = useVideoGain(videoElementRef, appStateStore.currentVideo?.defaultAudioStream);
 
+  const previewSubtitleStream = appStateStore.currentVideo?.previewSubtitleStream ?? null;
+  const subtitleTracksCount =
+    appStateStore.currentVideo?.subtitleStreams.filter((x) => x.path).length ?? 0;
+  useEffect(() => {
+    const videoElement = videoElementRef.current;
+    if (!videoElement) return;
+
+    for (const track of Array.from(videoElement.textTracks)) {
+      track.mode = track.id == `subtitles-${previewSubtitleStream}` ? "showing" : "disabled";
+    }
+  }, [previewSubtitleStream, subtitleTracksCount]);
+
   useEffect(() => {
     if (backConfirmation) {
       const t = setTimeout(() => setBackConfirmation(false), 3500);
@@ -374,6 +390,9 @@ const VideoView = observer(() => {
         KeyR: () => {
           appStateStore.currentVideo?.handlePlayFromStart();
         },
+        KeyM: () => {
+          appStateStore.currentVideo?.addChapterMarker();
+        },
       };
 
       const action = keyCodeActions[e.code];
@@ -428,6 +447,30 @@ const VideoView = observer(() => {
     appStateStore.closeCurrentVideo();
   };
 
+  const trimKeyframesMismatch = appStateStore.currentVideo?.trimKeyframesMismatch;
+  const exportBurnInSubtitles = appStateStore.currentVideo?.exportBurnInSubtitles;
+  const burnInSubtitlesValue =
+    exportBurnInSubtitles?.type == "stream"
+      ? String(exportBurnInSubtitles.index)
+      : exportBurnInSubtitles?.type == "file"
+        ? "file"
+        : "";
+
+  const handleBurnInSubtitlesChange = async (value: string) => {
+    const currentVideo = appStateStore.currentVideo;
+    if (!currentVideo) return;
+
+    if (value == "") {
+      currentVideo.setExportBurnInSubtitles(null);
+    } else if (value == "file") {
+      // Picked in the backend, so exports are allowed to read the file
+      const path = await selectSubtitlesFile();
+      if (path) currentVideo.setExportBurnInSubtitles({type: "file", path});
+    } else {
+      currentVideo.setExportBurnInSubtitles({type: "stream", index: parseInt(value)});
+    }
+  };
+
   const handleClickSelectExportPath = async () => {
     const path = await save({
       filters: [
@@ -462,7 +505,20 @@ const VideoView = observer(() => {
             src={appStateStore.currentVideo.getVideoPath()}
             onClick={handleVideoClicked}
             onDoubleClick={handleVideoDoubleClicked}
-          />
+          >
+            {appStateStore.currentVideo.subtitleStreams
+              .filter((x) => x.path)
+              .map((subtitleStream) => (
+                <track
+                  key={subtitleStream.streamIndex}
+                  id={`subtitles-${subtitleStream.streamIndex}`}
+                  kind="subtitles"
+                  label={subtitleStream.label}
+                  srcLang={subtitleStream.language ?? undefined}
+                  src={appStateStore.currentVideo!.getSubtitleStreamPath(subtitleStream)}
+                />
+              ))}
+          </Video>
           {appStateStore.currentVideo.videoPlayerError && (
             <VideoOverlayControlsWrapper
               align={"center"}
@@ -554,6 +610,30 @@ const VideoView = observer(() => {
               </Stack>
             );
           })}
+          {appStateStore.currentVideo.subtitleStreams.some((x) => x.path) && (
+            <TextField
+              select
+              size="small"
+              label="Subtitles"
+              sx={{maxWidth: 300, marginTop: 1}}
+              value={appStateStore.currentVideo.previewSubtitleStream ?? ""}
+              onChange={(e) =>
+                appStateStore.currentVideo?.setPreviewSubtitleStream(
+                  e.target.value === "" ? null : Number(e.target.value),
+                )
+              }
+            >
+              <MenuItem value={""}>None</MenuItem>
+              {appStateStore.currentVideo.subtitleStreams
+                .filter((x) => x.path)
+                .map((subtitleStream) => (
+                  <MenuItem key={subtitleStream.streamIndex} value={subtitleStream.streamIndex}>
+                    {subtitleStream.label}
+                  </MenuItem>
+                ))}
+            </TextField>
+          )}
+          <ChapterControls />
         </FormGroup>
       </Controls>
 
@@ -698,6 +778,87 @@ const VideoView = observer(() => {
                   </Select>
                 </FormControl>
               </Grid>
+              {appStateStore.currentVideo.subtitleStreams.length > 0 && (
+                <Grid size={6}>
+                  <Autocomplete
+                    multiple
+                    options={appStateStore.currentVideo.subtitleStreams}
+                    getOptionLabel={(option) => option.label}
+                    isOptionEqualToValue={(option, value) => option.streamIndex == value.streamIndex}
+                    value={appStateStore.currentVideo.subtitleStreams.filter((x) =>
+                      appStateStore.currentVideo!.exportSubtitleStreams.includes(x.streamIndex),
+                    )}
+                    onChange={(e, newValue) =>
+                      appStateStore.currentVideo?.setExportSubtitleStreams(
+                        newValue.map((x) => x.streamIndex),
+                      )
+                    }
+                    renderInput={(params) => (
+                      <TextField {...params} fullWidth label="Keep subtitles" />
+                    )}
+                  />
+                </Grid>
+              )}
+              <Grid size={6}>
+                <TextField
+                  select
+                  fullWidth
+                  label="Burn in subtitles"
+                  value={burnInSubtitlesValue}
+                  onChange={(e) => handleBurnInSubtitlesChange(e.target.value)}
+                  helperText={
+                    appStateStore.currentVideo.exportBurnInSubtitles?.type == "file" &&
+                    appStateStore.currentVideo.exportBurnInSubtitles.path
+                  }
+                >
+                  <MenuItem value={""}>None</MenuItem>
+                  {appStateStore.currentVideo.subtitleStreams.map((subtitleStream) => (
+                    <MenuItem key={subtitleStream.streamIndex} value={String(subtitleStream.streamIndex)}>
+                      {subtitleStream.label}
+                    </MenuItem>
+                  ))}
+                  <MenuItem value={"file"}>External file...</MenuItem>
+                </TextField>
+              </Grid>
+              <Grid size={6}>
+                <TextField
+                  select
+                  fullWidth
+                  label="Chapters"
+                  value={appStateStore.currentVideo.exportChapters}
+                  onChange={(e) =>
+                    appStateStore.currentVideo?.setExportChapters(
+                      e.target.value as ExportChapters["type"],
+                    )
+                  }
+                >
+                  <MenuItem value={"source"}>
+                    Keep source chapters ({appStateStore.currentVideo.chapters.length})
+                  </MenuItem>
+                  <MenuItem
+                    value={"markers"}
+                    disabled={appStateStore.currentVideo.chapterMarkers.length == 0}
+                  >
+                    From markers ({appStateStore.currentVideo.chapterMarkers.length})
+                  </MenuItem>
+                  <MenuItem value={"remove"}>None</MenuItem>
+                </TextField>
+              </Grid>
+              {trimKeyframesMismatch && (
+                <Grid size={12}>
+                  <Typography variant={"body2"} color={"warning"}>
+                    Trim points are not on keyframes, in a lossless cut{" "}
+                    {[
+                      trimKeyframesMismatch.start != null &&
+                        `the start would move to ${valuetext(trimKeyframesMismatch.start)}`,
+                      trimKeyframesMismatch.end != null &&
+                        `the end would move to ${valuetext(trimKeyframesMismatch.end)}`,
+                    ]
+                      .filter(Boolean)
+                      .join(" and ")}
+                  </Typography>
+                </Grid>
+              )}
             </Grid>
           </form>
         </DialogContent>
@@ -852,6 +1013,11 @@ const Timeline = observer(() => {
     appStateStore.currentVideo.updateVideoTrimValues(v[0], v[1]);
   };
 
+  const chapterMarks = [
+    ...appStateStore.currentVideo.chapters.map((x) => ({value: x.start})),
+    ...appStateStore.currentVideo.chapterMarkers.map((x) => ({value: x.time})),
+  ];
+
   useEffect(() => {
     const dispose = autorun(() => {
       if (!appStateStore.currentVideo) return;
@@ -872,6 +1038,7 @@ const Timeline = observer(() => {
           valueLabelFormat={valuetext}
           max={appStateStore.currentVideo.duration ?? 0}
           step={0.0001}
+          marks={chapterMarks}
           slots={{
             rail: () => <></>,
             track: () => <></>,
@@ -969,6 +1136,47 @@ const AudioVolumeSlider = observer(({audioStream}: {audioStream: AudioStream}) =
   );
 });
 
+const ChapterControls = observer(() => {
+  const appStateStore = useContext(AppStateStoreContext);
+
+  if (!appStateStore.currentVideo) return null;
+
+  const chapters = appStateStore.currentVideo.chapters;
+  const currentChapter = appStateStore.currentVideo.currentChapter;
+
+  return (
+    <Stack direction={"row"} spacing={1} sx={{alignItems: "center", flexWrap: "wrap", marginTop: 1}}>
+      {chapters.length > 0 && (
+        <TextField
+          select
+          size="small"
+          label="Chapter"
+          sx={{minWidth: 200, maxWidth: 300}}
+          value={currentChapter ? chapters.indexOf(currentChapter) : ""}
+          onChange={(e) =>
+            appStateStore.currentVideo?.setVideoTime(chapters[Number(e.target.value)].start)
+          }
+        >
+          {chapters.map((chapter, index) => (
+            <MenuItem key={index} value={index}>
+              {chapter.title || `Chapter ${index + 1}`} ({valuetext(chapter.start)})
+            </MenuItem>
+          ))}
+        </TextField>
+      )}
+      {appStateStore.currentVideo.chapterMarkers.map((marker) => (
+        <Chip
+          key={marker.time}
+          label={valuetext(marker.time)}
+          icon={<BookmarkIcon />}
+          onClick={() => appStateStore.currentVideo?.setVideoTime(marker.time)}
+          onDelete={() => appStateStore.currentVideo?.removeChapterMarker(marker.time)}
+        />
+      ))}
+    </Stack>
+  );
+});
+
 const RangeButtons = observer(() => {
   const appStateStore = useContext(AppStateStoreContext);
 
@@ -1003,6 +1211,34 @@ const RangeButtons = observer(() => {
           Play from start
         </Button>
       </Tooltip>
+      <Tooltip
+        title={
+          appStateStore.currentVideo.keyframes == null
+            ? "Indexing keyframes..."
+            : "Move the trim points to the nearest keyframes, so lossless cuts keep them"
+        }
+        disableInteractive
+      >
+        <span>
+          <Button
+            variant={"outlined"}
+            disabled={!appStateStore.currentVideo.trimKeyframesMismatch}
+            onClick={() => appStateStore.currentVideo?.snapTrimToKeyframes()}
+            startIcon={<KeyIcon />}
+          >
+            Snap to keyframes
+          </Button>
+        </span>
+      </Tooltip>
+      <Tooltip title="Hotkey: [M]" disableInteractive>
+        <Button
+          variant={"outlined"}
+          onClick={() => appStateStore.currentVideo?.addChapterMarker()}
+          startIcon={<BookmarkAddIcon />}
+        >
+          Add chapter
+        </Button>
+      </Tooltip>
     </Stack>
   );
 });
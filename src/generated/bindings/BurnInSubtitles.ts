@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type BurnInSubtitles = { "type": "stream", 
+/**
+ * Subtitle stream of the input by its source index
+ */
+index: number, } | { "type": "file", 
+/**
+ * External subtitle file, e.g. `.srt` or `.ass`, timed from the start of the input
+ */
+path: string, };
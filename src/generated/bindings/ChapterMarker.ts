@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Chapter start in the input timeline
+ */
+export type ChapterMarker = { time: number, title: string | null, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ExportAudioStreamOptions } from "./ExportAudioStreamOptions";
+
+export type ConcatClip = { inputPath: string, startTime: number, endTime: number, activeAudioStreams: Array<ExportAudioStreamOptions>, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConcatClip } from "./ConcatClip";
+
+/**
+ * Options of joining clips of one or several files into a single video
+ */
+export type ConcatOptions = { clips: Array<ConcatClip>, outputPath: string, 
+/**
+ * Common resolution of the output, e.g. `1920:1080`, the first clip resolution is used if not set
+ */
+resolution: string | null, 
+/**
+ * Common frame rate of the output, the first clip frame rate is used if not set
+ */
+frameRate: number | null, 
+/**
+ * Common audio sample rate of the output, 48 kHz is used if not set
+ */
+sampleRate: number | null, bitrate: string | null, videoCodec: string | null, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChapterMarker } from "./ChapterMarker";
+
+export type ExportChapters = { "type": "source" } | { "type": "markers", markers: Array<ChapterMarker>, } | { "type": "remove" };
@@ -1,5 +1,62 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BurnInSubtitles } from "./BurnInSubtitles";
 import type { ExportAudioStreamOptions } from "./ExportAudioStreamOptions";
+import type { ExportChapters } from "./ExportChapters";
+import type { ExportMode } from "./ExportMode";
 import type { GpuAcceleration } from "./GpuAcceleration";
+import type { RateControl } from "./RateControl";
+import type { TimeRange } from "./TimeRange";
 
-export type ExportOptions = { startTime: number, endTime: number, inputPath: string, outputPath: string, resolution: string | null, bitrate: string | null, videoCodec: string | null, frameRate: number | null, activeAudioStreams: Array<ExportAudioStreamOptions>, gpuAcceleration: GpuAcceleration | null, };
+export type ExportOptions = { startTime: number, endTime: number, inputPath: string, outputPath: string, resolution: string | null, bitrate: string | null, videoCodec: string | null, frameRate: number | null, activeAudioStreams: Array<ExportAudioStreamOptions>, gpuAcceleration: GpuAcceleration | null, mode: ExportMode, 
+/**
+ * Ranges to keep and join, `start_time`..`end_time` is used when empty
+ */
+segments: Array<TimeRange>, 
+/**
+ * Ranges to cut out, the rest of the video is kept
+ */
+removedSegments: Array<TimeRange>, 
+/**
+ * Duration of crossfades between joined segments in seconds
+ */
+crossfadeDuration: number | null, 
+/**
+ * Output size limit, the video bitrate is calculated from it and `bitrate` is ignored
+ */
+targetSizeBytes: bigint | null, 
+/**
+ * Video rate control, `bitrate` is used as average bitrate when not set
+ */
+rateControl: RateControl | null, 
+/**
+ * x264 style preset name (`ultrafast`..`veryslow`) mapped to the selected encoder or an encoder specific preset
+ */
+encoderPreset: string | null, encoderTune: string | null, 
+/**
+ * ffmpeg audio encoder, e.g. `aac` or `libopus`, the container default when not set
+ */
+audioCodec: string | null, audioBitrate: string | null, audioSampleRate: number | null, audioChannels: number | null, 
+/**
+ * Replace audio options the output container or codec can't handle instead of rejecting the export
+ */
+fixIncompatibleAudio: boolean, 
+/**
+ * Keep each active audio stream as its own output track instead of mixing them into one
+ */
+separateAudioTracks: boolean, 
+/**
+ * Source index of the stream whose track is marked default, the first active stream when not set
+ */
+defaultAudioStream: number | null, 
+/**
+ * Source indices of subtitle streams kept in the output, shifted to the exported ranges
+ */
+subtitleStreams: Array<number>, 
+/**
+ * Subtitles rendered into the video, needs re-encoding
+ */
+burnInSubtitles: BurnInSubtitles | null, 
+/**
+ * Chapters written to the output, rebased to the exported ranges
+ */
+chapters: ExportChapters, };
@@ -1,4 +1,9 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { AudioStreamFilePath } from "./AudioStreamFilePath";
+import type { SubtitleStreamFilePath } from "./SubtitleStreamFilePath";
 
-export type FfmpegAudioExtractTaskResult = { audio_streams: Array<AudioStreamFilePath>, };
+export type FfmpegAudioExtractTaskResult = { audio_streams: Array<AudioStreamFilePath>, 
+/**
+ * Text subtitle streams converted to WebVTT
+ */
+subtitle_streams: Array<SubtitleStreamFilePath>, };
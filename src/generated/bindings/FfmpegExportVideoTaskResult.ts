@@ -1,3 +1,8 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TimeRange } from "./TimeRange";
 
-export type FfmpegExportVideoTaskResult = { output_path: string, };
+export type FfmpegExportVideoTaskResult = { output_path: string, 
+/**
+ * Cut points actually used, may differ from requested ones in stream copy mode
+ */
+start_time: number, end_time: number, segments: Array<TimeRange>, };
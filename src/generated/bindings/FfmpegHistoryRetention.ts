@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How long finished, failed, cancelled and interrupted tasks are kept
+ */
+export type FfmpegHistoryRetention = { maxTasks: number | null, maxAgeDays: number | null, };
@@ -2,4 +2,25 @@
 import type { FfmpegTaskStatus } from "./FfmpegTaskStatus";
 import type { FfmpegTaskType } from "./FfmpegTaskType";
 
-export type FfmpegTask = { status: FfmpegTaskStatus, taskType: FfmpegTaskType, };
+export type FfmpegTask = { 
+/**
+ * Stable across queue changes and restarts
+ */
+id: number, status: FfmpegTaskStatus, taskType: FfmpegTaskType, 
+/**
+ * Queued tasks with higher priority start first, equal ones in queue order
+ */
+priority: number, 
+/**
+ * Unix time in milliseconds
+ */
+createdAt: number, 
+/**
+ * Unix time in milliseconds when the task finished, failed or was cancelled
+ */
+finishedAt: number | null, 
+/**
+ * Milliseconds the task was running, excluding pauses. Stored on the task instead of its result,
+ * so failed and cancelled tasks, which have no result, report it too.
+ */
+elapsedMillis: number | null, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FfmpegTaskErrorKind } from "./FfmpegTaskErrorKind";
+
+/**
+ * Reason of a failed task shown to the user
+ */
+export type FfmpegTaskFailure = { kind: FfmpegTaskErrorKind, message: string, 
+/**
+ * Last error lines printed by ffmpeg
+ */
+ffmpegErrors: Array<string>, };
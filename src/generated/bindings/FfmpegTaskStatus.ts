@@ -1,3 +1,16 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FfmpegTaskFailure } from "./FfmpegTaskFailure";
 
-export type FfmpegTaskStatus = { "type": "queued" } | { "type": "inProgress", progress: number, } | { "type": "finished" } | { "type": "failed" } | { "type": "cancelled" };
+export type FfmpegTaskStatus = { "type": "queued" } | { "type": "inProgress", progress: number, 
+/**
+ * Processed media seconds per second, smoothed
+ */
+speed: number | null, fps: number | null, bitrateKbps: number | null, 
+/**
+ * Bytes written to the output of the current ffmpeg step
+ */
+outputSize: number | null, 
+/**
+ * Estimated seconds left
+ */
+etaSeconds: number | null, } | { "type": "paused", progress: number, } | { "type": "finished" } | { "type": "failed", reason: FfmpegTaskFailure, } | { "type": "cancelled" } | { "type": "interrupted" };
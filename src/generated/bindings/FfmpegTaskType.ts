@@ -1,7 +1,9 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConcatOptions } from "./ConcatOptions";
 import type { ExportOptions } from "./ExportOptions";
 import type { FfmpegAudioExtractTaskResult } from "./FfmpegAudioExtractTaskResult";
+import type { FfmpegConcatVideosTaskResult } from "./FfmpegConcatVideosTaskResult";
 import type { FfmpegDownloadTaskResult } from "./FfmpegDownloadTaskResult";
 import type { FfmpegExportVideoTaskResult } from "./FfmpegExportVideoTaskResult";
 
-export type FfmpegTaskType = { "type": "extractAudio", videoFilePath: string, result: FfmpegAudioExtractTaskResult | null, } | { "type": "exportVideo", options: ExportOptions, result: FfmpegExportVideoTaskResult | null, } | { "type": "downloadFfmpeg", result: FfmpegDownloadTaskResult | null, };
+export type FfmpegTaskType = { "type": "extractAudio", videoFilePath: string, result: FfmpegAudioExtractTaskResult | null, } | { "type": "exportVideo", options: ExportOptions, result: FfmpegExportVideoTaskResult | null, } | { "type": "concatVideos", options: ConcatOptions, result: FfmpegConcatVideosTaskResult | null, } | { "type": "downloadFfmpeg", result: FfmpegDownloadTaskResult | null, };
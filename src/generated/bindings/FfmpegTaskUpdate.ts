@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FfmpegTask } from "./FfmpegTask";
+import type { FfmpegTaskStatus } from "./FfmpegTaskStatus";
+
+export type FfmpegTaskUpdate = { "type": "changed", task: FfmpegTask, } | { "type": "progress", taskId: number, status: FfmpegTaskStatus, } | { "type": "removed", taskIds: Array<number>, } | { "type": "reordered", taskIds: Array<number>, };
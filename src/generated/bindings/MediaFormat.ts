@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type MediaFormat = { 
+/**
+ * Comma separated demuxer names, e.g. `mov,mp4,m4a,3gp,3g2,mj2`
+ */
+formatName: string | null, formatLongName: string | null, 
+/**
+ * Timestamp of the first frame, ffmpeg cut times count from it
+ */
+startTime: number | null, duration: number | null, size: number | null, bitRate: number | null, tags: Record<string, string>, };
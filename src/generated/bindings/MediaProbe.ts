@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MediaChapter } from "./MediaChapter";
+import type { MediaFormat } from "./MediaFormat";
+import type { MediaStream } from "./MediaStream";
+
+/**
+ * Everything the app needs to know about a media file
+ */
+export type MediaProbe = { format: MediaFormat, streams: Array<MediaStream>, chapters: Array<MediaChapter>, };
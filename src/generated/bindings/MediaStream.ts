@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MediaStreamDisposition } from "./MediaStreamDisposition";
+import type { MediaStreamKind } from "./MediaStreamKind";
+
+export type MediaStream = { index: number, kind: MediaStreamKind, codecName: string | null, profile: string | null, level: number | null, timeBase: string | null, width: number | null, height: number | null, 
+/**
+ * Base frame rate of the stream
+ */
+rFrameRate: number | null, 
+/**
+ * Average frame rate, differs from `r_frame_rate` for variable frame rate video
+ */
+avgFrameRate: number | null, pixFmt: string | null, colorSpace: string | null, colorTransfer: string | null, colorPrimaries: string | null, colorRange: string | null, bitDepth: number | null, 
+/**
+ * Rotation in degrees from the display matrix, counter-clockwise
+ */
+rotation: number | null, bitRate: number | null, sampleRate: number | null, channels: number | null, channelLayout: string | null, duration: number | null, language: string | null, title: string | null, disposition: MediaStreamDisposition, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type MediaStreamDisposition = { default: boolean, forced: boolean, dub: boolean, original: boolean, comment: boolean, hearingImpaired: boolean, visualImpaired: boolean, 
+/**
+ * Cover art stored as a video stream
+ */
+attachedPic: boolean, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Video rate control, `quality` is CRF for x264/x265/SVT-AV1 and CQ for NVENC
+ */
+export type RateControl = { "type": "constantQuality", quality: number, } | { "type": "averageBitrate", bitrate: string, } | { "type": "constrainedVbr", bitrate: string, maxBitrate: string, bufferSize: string, };
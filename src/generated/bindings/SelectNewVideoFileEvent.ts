@@ -1,5 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { AudioStreamFilePath } from "./AudioStreamFilePath";
 import type { SelectedVideoFile } from "./SelectedVideoFile";
+import type { SubtitleStreamFilePath } from "./SubtitleStreamFilePath";
 
-export type SelectNewVideoFileEvent = { "event": "videoFilePicked" } | { "event": "videoFileInfoReady", videoFile: SelectedVideoFile | null, } | { "event": "videoAudioSteamsReady", videoFile: string, audioStreams: Array<AudioStreamFilePath>, };
+export type SelectNewVideoFileEvent = { "event": "videoFilePicked" } | { "event": "videoFileInfoReady", videoFile: SelectedVideoFile | null, } | { "event": "videoAudioSteamsReady", videoFile: string, audioStreams: Array<AudioStreamFilePath>, } | { "event": "videoSubtitleStreamsReady", videoFile: string, subtitleStreams: Array<SubtitleStreamFilePath>, } | { "event": "videoFileProbeFailed", videoFile: string, error: string, };
@@ -8,6 +8,8 @@
 import { invoke } from '@tauri-apps/api/core';
 
 import * as types from './types';
+import type { FfmpegTask } from './bindings/FfmpegTask';
+import type { MediaProbe } from './bindings/MediaProbe';
 
 export async function getIntegratedServerState(): Promise<types.IntegratedServerStarted | null> {
   return invoke('get_integrated_server_state');
@@ -17,19 +19,94 @@ export async function getLogs(): Promise<types.LogRecord[]> {
   return invoke('get_logs');
 }
 
+export async function getFfmpegQueue(): Promise<FfmpegTask[]> {
+  return invoke('get_ffmpeg_queue');
+}
+
 export async function ffmpegExport(params: types.FfmpegExportParams): Promise<void> {
   return invoke('ffmpeg_export', params);
 }
 
-export async function cancelFfmpegTaskByIndex(params: types.CancelFfmpegTaskByIndexParams): Promise<void> {
-  return invoke('cancel_ffmpeg_task_by_index', params);
+export async function ffmpegConcat(params: types.FfmpegConcatParams): Promise<void> {
+  return invoke('ffmpeg_concat', params);
+}
+
+export async function cancelFfmpegTaskById(params: types.CancelFfmpegTaskByIdParams): Promise<void> {
+  return invoke('cancel_ffmpeg_task_by_id', params);
+}
+
+export async function pauseFfmpegTaskById(params: types.PauseFfmpegTaskByIdParams): Promise<void> {
+  return invoke('pause_ffmpeg_task_by_id', params);
+}
+
+export async function resumeFfmpegTaskById(params: types.ResumeFfmpegTaskByIdParams): Promise<void> {
+  return invoke('resume_ffmpeg_task_by_id', params);
+}
+
+export async function moveFfmpegTaskById(params: types.MoveFfmpegTaskByIdParams): Promise<void> {
+  return invoke('move_ffmpeg_task_by_id', params);
+}
+
+export async function setFfmpegTaskPriorityById(params: types.SetFfmpegTaskPriorityByIdParams): Promise<void> {
+  return invoke('set_ffmpeg_task_priority_by_id', params);
+}
+
+export async function retryFfmpegTaskById(params: types.RetryFfmpegTaskByIdParams): Promise<void> {
+  return invoke('retry_ffmpeg_task_by_id', params);
+}
+
+export async function duplicateFfmpegTaskById(params: types.DuplicateFfmpegTaskByIdParams): Promise<number> {
+  return invoke('duplicate_ffmpeg_task_by_id', params);
+}
+
+export async function removeFfmpegTaskById(params: types.RemoveFfmpegTaskByIdParams): Promise<void> {
+  return invoke('remove_ffmpeg_task_by_id', params);
+}
+
+export async function clearFinishedFfmpegTasks(): Promise<void> {
+  return invoke('clear_finished_ffmpeg_tasks');
 }
 
 export async function selectNewVideoFile(): Promise<void> {
   return invoke('select_new_video_file');
 }
 
+export async function selectSubtitlesFile(): Promise<string | null> {
+  return invoke('select_subtitles_file');
+}
+
 export async function openDevtools(): Promise<void> {
   return invoke('open_devtools');
 }
 
+export async function getFfmpegConcurrencyLimits(): Promise<types.FfmpegConcurrencyLimits> {
+  return invoke('get_ffmpeg_concurrency_limits');
+}
+
+export async function setFfmpegConcurrencyLimits(params: types.SetFfmpegConcurrencyLimitsParams): Promise<void> {
+  return invoke('set_ffmpeg_concurrency_limits', params);
+}
+
+export async function getFfmpegHistoryRetention(): Promise<types.FfmpegHistoryRetention> {
+  return invoke('get_ffmpeg_history_retention');
+}
+
+export async function setFfmpegHistoryRetention(params: types.SetFfmpegHistoryRetentionParams): Promise<void> {
+  return invoke('set_ffmpeg_history_retention', params);
+}
+
+export async function getFfmpegTaskLog(params: types.GetFfmpegTaskLogParams): Promise<types.FfmpegRunLogRecord[]> {
+  return invoke('get_ffmpeg_task_log', params);
+}
+
+export async function saveFfmpegTaskLog(params: types.SaveFfmpegTaskLogParams): Promise<void> {
+  return invoke('save_ffmpeg_task_log', params);
+}
+
+export async function probeMediaFile(params: types.ProbeMediaFileParams): Promise<MediaProbe> {
+  return invoke('probe_media_file', params);
+}
+
+export async function getVideoKeyframeIndex(params: types.GetVideoKeyframeIndexParams): Promise<number[]> {
+  return invoke('get_video_keyframe_index', params);
+}
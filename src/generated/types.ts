@@ -10,15 +10,103 @@ export interface FfmpegExportParams {
   [key: string]: unknown;
 }
 
-export interface CancelFfmpegTaskByIndexParams {
-  taskIndex: number;
+export interface FfmpegConcatParams {
+  options: ConcatOptions;
   [key: string]: unknown;
 }
 
+export interface CancelFfmpegTaskByIdParams {
+  taskId: number;
+  [key: string]: unknown;
+}
+
+export interface PauseFfmpegTaskByIdParams {
+  taskId: number;
+  [key: string]: unknown;
+}
+
+export interface ResumeFfmpegTaskByIdParams {
+  taskId: number;
+  [key: string]: unknown;
+}
+
+export interface MoveFfmpegTaskByIdParams {
+  taskId: number;
+  taskMove: FfmpegTaskMove;
+  [key: string]: unknown;
+}
+
+export interface SetFfmpegTaskPriorityByIdParams {
+  taskId: number;
+  priority: number;
+  [key: string]: unknown;
+}
+
+export interface RetryFfmpegTaskByIdParams {
+  taskId: number;
+  [key: string]: unknown;
+}
+
+export interface DuplicateFfmpegTaskByIdParams {
+  taskId: number;
+  options?: ExportOptions;
+  [key: string]: unknown;
+}
+
+export interface RemoveFfmpegTaskByIdParams {
+  taskId: number;
+  [key: string]: unknown;
+}
+
+export interface SetFfmpegConcurrencyLimitsParams {
+  limits: FfmpegConcurrencyLimits;
+  [key: string]: unknown;
+}
+
+export interface SetFfmpegHistoryRetentionParams {
+  retention: FfmpegHistoryRetention;
+  [key: string]: unknown;
+}
+
+export interface GetFfmpegTaskLogParams {
+  taskId: number;
+  [key: string]: unknown;
+}
+
+export interface SaveFfmpegTaskLogParams {
+  taskId: number;
+  path: string;
+  [key: string]: unknown;
+}
+
+export interface ProbeMediaFileParams {
+  path: string;
+  [key: string]: unknown;
+}
+
+export interface GetVideoKeyframeIndexParams {
+  path: string;
+  [key: string]: unknown;
+}
+
+export interface FfmpegHistoryRetention {
+  maxTasks?: number | null;
+  maxAgeDays?: number | null;
+}
+
+export interface FfmpegConcurrencyLimits {
+  interactive: number;
+  export: number;
+}
+
+export type FfmpegTaskMove = "up" | "down" | "front";
+
 export type LogLevel = "Trace" | "Debug" | "Info" | "Warn" | "Error";
 
 export type GpuAcceleration = "nvidia";
 
+export type ExportMode = "reencode" | "streamCopy" | "smartRender";
+
 export interface IntegratedServerStarted {
   port: number;
 }
@@ -34,6 +122,57 @@ export interface ExportOptions {
   frameRate?: number | null;
   activeAudioStreams: ExportAudioStreamOptions[];
   gpuAcceleration?: GpuAcceleration | null;
+  mode?: ExportMode;
+  segments?: TimeRange[];
+  removedSegments?: TimeRange[];
+  crossfadeDuration?: number | null;
+  targetSizeBytes?: number | null;
+  rateControl?: RateControl | null;
+  encoderPreset?: string | null;
+  encoderTune?: string | null;
+  audioCodec?: string | null;
+  audioBitrate?: string | null;
+  audioSampleRate?: number | null;
+  audioChannels?: number | null;
+  fixIncompatibleAudio?: boolean;
+  separateAudioTracks?: boolean;
+  defaultAudioStream?: number | null;
+  subtitleStreams?: number[];
+  burnInSubtitles?: BurnInSubtitles | null;
+  chapters?: ExportChapters;
+}
+
+export type ExportChapters = { type: "source" } | { type: "markers"; markers: ChapterMarker[] } | { type: "remove" };
+
+export interface ChapterMarker {
+  time: number;
+  title?: string | null;
+}
+
+export type BurnInSubtitles = { type: "stream"; index: number } | { type: "file"; path: string };
+
+export type RateControl = { type: "constantQuality"; quality: number } | { type: "averageBitrate"; bitrate: string } | { type: "constrainedVbr"; bitrate: string; maxBitrate: string; bufferSize: string };
+
+export interface TimeRange {
+  start: number;
+  end: number;
+}
+
+export interface ConcatOptions {
+  clips: ConcatClip[];
+  outputPath: string;
+  resolution?: string | null;
+  frameRate?: number | null;
+  sampleRate?: number | null;
+  bitrate?: string | null;
+  videoCodec?: string | null;
+}
+
+export interface ConcatClip {
+  inputPath: string;
+  startTime: number;
+  endTime: number;
+  activeAudioStreams: ExportAudioStreamOptions[];
 }
 
 export interface ExportAudioStreamOptions {
@@ -41,6 +180,13 @@ export interface ExportAudioStreamOptions {
   gain: number;
 }
 
+export interface FfmpegRunLogRecord {
+  command: string;
+  lines: string[];
+  droppedLines: number;
+  exitStatus?: string | null;
+}
+
 export interface LogRecord {
   message: string;
   level: LogLevel;
@@ -0,0 +1,14 @@
+/**
+ * Format duration as h:mm:ss or m:ss
+ * @param {number} seconds - Duration in seconds
+ */
+function formatDuration(seconds: number) {
+  const totalSeconds = Math.round(seconds);
+  const hours = Math.floor(totalSeconds / 3600);
+  const minutes = Math.floor((totalSeconds % 3600) / 60);
+  const secs = String(totalSeconds % 60).padStart(2, "0");
+
+  return hours > 0 ? `${hours}:${String(minutes).padStart(2, "0")}:${secs}` : `${minutes}:${secs}`;
+}
+
+export default formatDuration;
@@ -14,6 +14,7 @@ class AppStateStore {
 
   filePickingInProgress = false;
   fileProcessingInfo = false;
+  fileProbeError: string | null = null;
 
   integratedServerStatus: IntegratedServerStarted | null = null;
 
@@ -30,6 +31,10 @@ class AppStateStore {
     await selectNewVideoFile();
   }
 
+  dismissFileProbeError() {
+    this.fileProbeError = null;
+  }
+
   closeCurrentVideo() {
     this.currentVideo = null;
   }
@@ -68,11 +73,20 @@ class AppStateStore {
             }
             this.fileProcessingInfo = false;
             break;
+          case "videoFileProbeFailed":
+            // The video is opened without stream info right after
+            this.fileProbeError = e.payload.error;
+            break;
           case "videoAudioSteamsReady":
             if (!this.currentVideo) return;
             if (this.currentVideo.path != e.payload.videoFile) return;
             this.currentVideo.updateAudioStreamsFilePaths(e.payload.audioStreams);
             break;
+          case "videoSubtitleStreamsReady":
+            if (!this.currentVideo) return;
+            if (this.currentVideo.path != e.payload.videoFile) return;
+            this.currentVideo.updateSubtitleStreamsFilePaths(e.payload.subtitleStreams);
+            break;
         }
       });
     });
@@ -1,7 +1,22 @@
 import {makeAutoObservable, runInAction} from "mobx";
 import {FfmpegTask} from "../generated/bindings/FfmpegTask.ts";
+import {FfmpegTaskUpdate} from "../generated/bindings/FfmpegTaskUpdate.ts";
 import {AsyncEventsDisposer} from "../lib/createAsyncEventsDisposer.ts";
-import {cancelFfmpegTaskByIndex} from "../generated";
+import {
+  cancelFfmpegTaskById,
+  clearFinishedFfmpegTasks,
+  duplicateFfmpegTaskById,
+  getFfmpegQueue,
+  ExportOptions,
+  FfmpegTaskMove,
+  moveFfmpegTaskById,
+  pauseFfmpegTaskById,
+  removeFfmpegTaskById,
+  resumeFfmpegTaskById,
+  retryFfmpegTaskById,
+  saveFfmpegTaskLog,
+} from "../generated";
+import {save} from "@tauri-apps/plugin-dialog";
 
 class FfmpegTasksQueue {
   ffmpegTasks: FfmpegTask[] = [];
@@ -21,15 +36,88 @@ class FfmpegTasksQueue {
   }
 
   async listenToFfmpegEvents(disposer: AsyncEventsDisposer) {
-    await disposer.addListener<FfmpegTask[]>("ffmpeg-queue", (message) => {
-      runInAction(() => {
-        this.ffmpegTasks = message.payload;
-      });
+    // Updates arriving before the queue snapshot are replayed on top of it instead of being lost
+    let pendingUpdates: FfmpegTaskUpdate[] | null = [];
+    await disposer.addListener<FfmpegTaskUpdate>("ffmpeg-task-update", (message) => {
+      if (pendingUpdates) pendingUpdates.push(message.payload);
+      else this.applyUpdate(message.payload);
     });
+
+    // Updates only carry changes, so start from the current queue
+    const ffmpegTasks = await getFfmpegQueue();
+    runInAction(() => {
+      this.ffmpegTasks = ffmpegTasks;
+      pendingUpdates?.forEach(this.applyUpdate);
+      pendingUpdates = null;
+    });
+  }
+
+  applyUpdate(update: FfmpegTaskUpdate) {
+    switch (update.type) {
+      case "changed": {
+        const index = this.ffmpegTasks.findIndex((x) => x.id == update.task.id);
+        if (index == -1) this.ffmpegTasks.push(update.task);
+        else this.ffmpegTasks[index] = update.task;
+        break;
+      }
+      case "progress": {
+        const task = this.ffmpegTasks.find((x) => x.id == update.taskId);
+        if (task) task.status = update.status;
+        break;
+      }
+      case "removed":
+        this.ffmpegTasks = this.ffmpegTasks.filter((x) => !update.taskIds.includes(x.id));
+        break;
+      case "reordered": {
+        const order = new Map(update.taskIds.map((id, index) => [id, index]));
+        this.ffmpegTasks = this.ffmpegTasks
+          .slice()
+          .sort((a, b) => (order.get(a.id) ?? Infinity) - (order.get(b.id) ?? Infinity));
+        break;
+      }
+    }
+  }
+
+  async cancelTask(taskId: number) {
+    await cancelFfmpegTaskById({taskId});
+  }
+
+  async pauseTask(taskId: number) {
+    await pauseFfmpegTaskById({taskId});
+  }
+
+  async resumeTask(taskId: number) {
+    await resumeFfmpegTaskById({taskId});
+  }
+
+  async moveTask(taskId: number, taskMove: FfmpegTaskMove) {
+    await moveFfmpegTaskById({taskId, taskMove});
+  }
+
+  async retryTask(taskId: number) {
+    await retryFfmpegTaskById({taskId});
   }
 
-  async cancelTask(taskIndex: number) {
-    await cancelFfmpegTaskByIndex({taskIndex});
+  async duplicateTask(taskId: number, options?: ExportOptions) {
+    return await duplicateFfmpegTaskById({taskId, options});
+  }
+
+  async removeTask(taskId: number) {
+    await removeFfmpegTaskById({taskId});
+  }
+
+  async clearFinishedTasks() {
+    await clearFinishedFfmpegTasks();
+  }
+
+  async saveTaskLog(taskId: number) {
+    const path = await save({
+      defaultPath: `ffmpeg-task-${taskId}.log`,
+      filters: [{name: "Log", extensions: ["log", "txt"]}],
+    });
+    if (!path) return;
+
+    await saveFfmpegTaskLog({taskId, path});
   }
 }
 
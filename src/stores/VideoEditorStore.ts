@@ -1,10 +1,21 @@
-import {makeAutoObservable} from "mobx";
+import {makeAutoObservable, runInAction} from "mobx";
 import {VideoAudioStreamsInfo} from "../generated/bindings/VideoAudioStreamsInfo.ts";
 import {AudioStreamFilePath} from "../generated/bindings/AudioStreamFilePath.ts";
 import addPostfixToFilename from "../lib/addPostfixToFilename.ts";
 import replaceExtension from "../lib/replaceExtension.ts";
 import estimateVideoSize from "../lib/estimateVideoSize.ts";
-import {ffmpegExport, GpuAcceleration} from "../generated";
+import {
+  BurnInSubtitles,
+  ChapterMarker,
+  ExportChapters,
+  ffmpegExport,
+  getVideoKeyframeIndex,
+  GpuAcceleration,
+  probeMediaFile,
+} from "../generated";
+import {MediaChapter} from "../generated/bindings/MediaChapter.ts";
+import {MediaProbe} from "../generated/bindings/MediaProbe.ts";
+import {SubtitleStreamFilePath} from "../generated/bindings/SubtitleStreamFilePath.ts";
 import {gainToGainValue} from "../lib/useVideoGain.ts";
 import convertFilePath from "../lib/convertFilePath.ts";
 import AppStateStore from "./AppStateStore.ts";
@@ -16,6 +27,14 @@ export interface AudioStream {
   path: string | null;
 }
 
+export interface SubtitleStream {
+  streamIndex: number;
+  label: string;
+  language: string | null;
+  /** WebVTT file for the preview, only text subtitles are converted */
+  path: string | null;
+}
+
 type Nullable<T> = {
   [P in keyof T]: T[P] | null;
 };
@@ -28,6 +47,8 @@ export interface VideoState {
 }
 
 const MINIMAL_SECONDS_DIFF = 1;
+/** Cut points closer than this to a keyframe are considered to be on it */
+const KEYFRAME_TOLERANCE_SECONDS = 0.001;
 
 class VideoEditorStore {
   appStateStore: AppStateStore;
@@ -94,6 +115,53 @@ class VideoEditorStore {
     this.trimEnd = this.videoState.time;
   }
 
+  /**
+   * Sorted keyframe timestamps of the video stream, counting from its start time like the trim
+   * points, `null` until they are indexed
+   */
+  keyframes: number[] | null = null;
+
+  async loadKeyframes() {
+    try {
+      const keyframes = await getVideoKeyframeIndex({path: this.path});
+      runInAction(() => (this.keyframes = keyframes));
+    } catch (e) {
+      console.error("Failed to index video keyframes", e);
+    }
+  }
+
+  nearestKeyframe(time: number) {
+    if (!this.keyframes?.length) return null;
+
+    return this.keyframes.reduce((nearest, keyframe) =>
+      Math.abs(keyframe - time) < Math.abs(nearest - time) ? keyframe : nearest,
+    );
+  }
+
+  /** Where lossless cuts would move the trim points, `null` for points already on a keyframe */
+  get trimKeyframesMismatch() {
+    if (this.trimStart == null || this.trimEnd == null) return null;
+
+    const start = this.nearestKeyframe(this.trimStart);
+    const end = this.nearestKeyframe(this.trimEnd);
+    const mismatch = {
+      start: start != null && Math.abs(start - this.trimStart) > KEYFRAME_TOLERANCE_SECONDS ? start : null,
+      end: end != null && Math.abs(end - this.trimEnd) > KEYFRAME_TOLERANCE_SECONDS ? end : null,
+    };
+
+    return mismatch.start != null || mismatch.end != null ? mismatch : null;
+  }
+
+  snapTrimToKeyframes() {
+    const mismatch = this.trimKeyframesMismatch;
+    if (!mismatch || this.trimStart == null || this.trimEnd == null) return;
+
+    const trimStart = mismatch.start ?? this.trimStart;
+    const trimEnd = mismatch.end != null && mismatch.end > trimStart ? mismatch.end : this.trimEnd;
+    this.trimStart = trimStart;
+    this.trimEnd = trimEnd;
+  }
+
   handlePlayFromStart() {
     this.setVideoTime(this.trimStart ?? 0);
   }
@@ -105,6 +173,61 @@ class VideoEditorStore {
     }
   }
 
+  subtitleStreamsFilePaths: SubtitleStreamFilePath[] = [];
+
+  updateSubtitleStreamsFilePaths(subtitleStreamsFilePaths: SubtitleStreamFilePath[]) {
+    this.subtitleStreamsFilePaths = subtitleStreamsFilePaths;
+  }
+
+  get subtitleStreams(): SubtitleStream[] {
+    return (this.mediaProbe?.streams ?? [])
+      .filter((x) => x.kind == "subtitle")
+      .map((x, index) => ({
+        streamIndex: x.index,
+        label: [x.title, x.language].filter(Boolean).join(" - ") || `Subtitles #${index + 1}`,
+        language: x.language,
+        path: this.subtitleStreamsFilePaths.find((p) => p.index == x.index)?.path ?? null,
+      }));
+  }
+
+  getSubtitleStreamPath(subtitleStream: SubtitleStream) {
+    return convertFilePath(subtitleStream.path ?? undefined, this.appStateStore.integratedServerStatus?.port);
+  }
+
+  previewSubtitleStream: number | null = null;
+
+  setPreviewSubtitleStream(streamIndex: number | null) {
+    this.previewSubtitleStream = streamIndex;
+  }
+
+  get chapters(): MediaChapter[] {
+    return this.mediaProbe?.chapters ?? [];
+  }
+
+  get currentChapter() {
+    const time = this.videoState.time;
+    return this.chapters.find((x) => x.start <= time && time < x.end) ?? null;
+  }
+
+  chapterMarkers: ChapterMarker[] = [];
+
+  /** Adds a marker starting a chapter at the current time and exports chapters from the markers */
+  addChapterMarker() {
+    const time = this.videoState.time;
+    if (this.chapterMarkers.some((x) => x.time == time)) return;
+
+    this.chapterMarkers.push({time, title: null});
+    this.chapterMarkers.sort((a, b) => a.time - b.time);
+    this.exportChapters = "markers";
+  }
+
+  removeChapterMarker(time: number) {
+    this.chapterMarkers = this.chapterMarkers.filter((x) => x.time != time);
+    if (this.chapterMarkers.length == 0 && this.exportChapters == "markers") {
+      this.exportChapters = "source";
+    }
+  }
+
   toggleAudioStream(streamIndex: number) {
     const index = this.audioStreams.findIndex((x) => x.streamIndex == streamIndex);
     if (index != -1) {
@@ -224,6 +347,56 @@ class VideoEditorStore {
     }
   }
 
+  mediaProbe: MediaProbe | null = null;
+
+  exportSubtitleStreams: number[] = [];
+
+  setExportSubtitleStreams(exportSubtitleStreams: number[]) {
+    this.exportSubtitleStreams = exportSubtitleStreams;
+  }
+
+  exportBurnInSubtitles: BurnInSubtitles | null = null;
+
+  setExportBurnInSubtitles(exportBurnInSubtitles: BurnInSubtitles | null) {
+    this.exportBurnInSubtitles = exportBurnInSubtitles;
+  }
+
+  exportChapters: ExportChapters["type"] = "source";
+
+  setExportChapters(exportChapters: ExportChapters["type"]) {
+    this.exportChapters = exportChapters;
+  }
+
+  /** Replaces guessed export defaults with the parameters of the source video */
+  async loadMediaProbe() {
+    let mediaProbe: MediaProbe;
+    try {
+      mediaProbe = await probeMediaFile({path: this.path});
+    } catch (e) {
+      console.error("Failed to probe video file", e);
+      return;
+    }
+
+    runInAction(() => {
+      this.mediaProbe = mediaProbe;
+      // Subtitles are kept unless they are deselected
+      this.exportSubtitleStreams = this.subtitleStreams.map((x) => x.streamIndex);
+
+      const videoStream = mediaProbe.streams.find((x) => x.kind == "video" && !x.disposition.attachedPic);
+      if (!videoStream) return;
+
+      if (videoStream.width && videoStream.height) {
+        const rotated = Math.abs(videoStream.rotation ?? 0) % 180 == 90;
+        this.exportResolution = rotated
+          ? `${videoStream.height}x${videoStream.width}`
+          : `${videoStream.width}x${videoStream.height}`;
+      }
+
+      const frameRate = videoStream.avgFrameRate ?? videoStream.rFrameRate;
+      if (frameRate) this.exportFrameRate = Math.round(frameRate * 100) / 100;
+    });
+  }
+
   async exportVideo() {
     await ffmpegExport({
       options: {
@@ -242,6 +415,12 @@ class VideoEditorStore {
             index: x.streamIndex,
             gain: gainToGainValue(x.gain),
           })),
+        subtitleStreams: this.exportSubtitleStreams,
+        burnInSubtitles: this.exportBurnInSubtitles,
+        chapters:
+          this.exportChapters == "markers"
+            ? {type: "markers", markers: this.chapterMarkers}
+            : {type: this.exportChapters},
       },
     });
   }
@@ -268,6 +447,8 @@ class VideoEditorStore {
 
     this.setVideoDuration(videoAudioStreamsInfo.duration);
     this.setExportPath(addPostfixToFilename(path, " - Trim"));
+    void this.loadMediaProbe();
+    void this.loadKeyframes();
   }
 }
 